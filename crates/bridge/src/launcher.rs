@@ -1,14 +1,23 @@
 use crate::{
-    neovide,
     plugin_config::{LauncherType, PluginConfig, SocketType},
+    rpc,
+    target::Target,
     utils::is_port_in_use,
 };
 use anyhow::{Context, Result, bail};
-use defold_nvim_core::{focus::focus_neovim, nvim_server, utils::classname};
+use defold_nvim_core::{
+    focus::{FocusSettings, focus_neovim},
+    instances::{self, Instance},
+    multiplexer::{self, Multiplexer},
+    nvim_server,
+    project_id::ProjectId,
+    tools,
+};
 use std::{
     fs::{self},
     path::{Path, PathBuf},
-    process::{Child, Command},
+    process::Child,
+    time::Duration,
 };
 use termlauncher::{Application, CustomTerminal, Terminal};
 use which::which;
@@ -16,6 +25,8 @@ use which::which;
 const ERR_NEOVIDE_NOT_FOUND: &str = "Could not find Neovide, have you installed it?";
 const ERR_TERMINAL_NOT_FOUND: &str = "Could not find any suitable terminal";
 
+const SERVER_STARTUP_TIMEOUT: Duration = Duration::from_secs(15);
+
 const VAR_CLASSNAME: &str = "{CLASSNAME}";
 const VAR_ADDRESS: &str = "{ADDR}";
 const VAR_LINE: &str = "{LINE}";
@@ -44,13 +55,51 @@ fn report_process_errors(mut child: Child) -> Result<()> {
     Ok(())
 }
 
-fn apply_launcher_vars(launcher: &Terminal, var: &str, replace_with: &str) -> Terminal {
+#[derive(Debug, Clone)]
+enum Launcher {
+    Terminal(Terminal),
+    Multiplexer(Multiplexer),
+}
+
+impl Launcher {
+    fn name(&self) -> String {
+        match self {
+            Launcher::Terminal(Terminal::Custom(term)) => Path::new(&term.executable)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(&term.executable)
+                .to_string(),
+            Launcher::Terminal(term) => format!("{term:?}").to_lowercase(),
+            Launcher::Multiplexer(mux) => format!("{mux:?}").to_lowercase(),
+        }
+    }
+
+    fn launch(&self, app: Application, root_dir: &Path) -> Result<()> {
+        match self {
+            Launcher::Terminal(term) => report_process_errors(app.launch_with(term)?),
+            Launcher::Multiplexer(mux) => {
+                let name = ProjectId::from_root(root_dir)?.classname();
+
+                let mut command = vec![app.command.clone()];
+                command.extend(app.args.iter().cloned());
+
+                multiplexer::spawn(*mux, root_dir, &name, &command, &app.env_vars)?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn apply_launcher_vars(launcher: &Launcher, var: &str, replace_with: &str) -> Launcher {
     match launcher {
-        Terminal::Custom(term) => Terminal::Custom(CustomTerminal {
-            arguments: apply_vars(&term.arguments, var, replace_with),
-            ..term.clone()
-        }),
-        term => term.clone(),
+        Launcher::Terminal(Terminal::Custom(term)) => {
+            Launcher::Terminal(Terminal::Custom(CustomTerminal {
+                arguments: apply_vars(&term.arguments, var, replace_with),
+                ..term.clone()
+            }))
+        }
+        launcher => launcher.clone(),
     }
 }
 
@@ -58,7 +107,7 @@ fn apply_vars(args: &[String], var: &str, replace_with: &str) -> Vec<String> {
     args.iter().map(|s| s.replace(var, replace_with)).collect()
 }
 
-fn create_launcher(cfg: &PluginConfig) -> Result<Terminal> {
+fn create_launcher(cfg: &PluginConfig, root_dir: &Path) -> Result<Launcher> {
     match cfg.launcher_type {
         Some(LauncherType::Neovide) => {
             let executable = &cfg
@@ -66,7 +115,7 @@ fn create_launcher(cfg: &PluginConfig) -> Result<Terminal> {
                 .as_ref()
                 .map(Into::into)
                 .or_else(|| which("neovide").ok())
-                .or_else(|| match neovide::install() {
+                .or_else(|| match tools::install("neovide", Some(root_dir)) {
                     Ok(path) => Some(path),
                     Err(err) => {
                         tracing::error!("Could not download neovide because: {err:?}");
@@ -89,7 +138,7 @@ fn create_launcher(cfg: &PluginConfig) -> Result<Terminal> {
                 args.push(VAR_CLASSNAME.to_string());
             }
 
-            Ok(Terminal::Custom(CustomTerminal {
+            Ok(Launcher::Terminal(Terminal::Custom(CustomTerminal {
                 executable: executable
                     .to_str()
                     .context("could not convert path to string")?
@@ -97,7 +146,7 @@ fn create_launcher(cfg: &PluginConfig) -> Result<Terminal> {
                 arguments: args,
                 run_arg: Some("--neovim-bin".to_string()),
                 ..Default::default()
-            }))
+            })))
         }
         Some(LauncherType::Terminal) => {
             // terminal specified by absolute path
@@ -113,14 +162,14 @@ fn create_launcher(cfg: &PluginConfig) -> Result<Terminal> {
 
                 tracing::debug!("Terminal specified by absolute path {term:?}");
 
-                Ok(term)
+                Ok(Launcher::Terminal(term))
             } else if let Some(exe_name) = &cfg.executable
                 && let Some(term) = Terminal::find_by_name(exe_name)
                 && term.is_available()
             {
                 tracing::debug!("Looking for terminal by name {exe_name} found {term:?}");
 
-                Ok(term)
+                Ok(Launcher::Terminal(term))
             } else {
                 tracing::debug!(
                     "No terminal specific terminal specified or not found, looking for available one..."
@@ -129,26 +178,35 @@ fn create_launcher(cfg: &PluginConfig) -> Result<Terminal> {
                 if let Some(term) = Terminal::find_available() {
                     tracing::debug!("Found {term:?}");
 
-                    Ok(term)
+                    Ok(Launcher::Terminal(term))
                 } else {
                     bail!(ERR_TERMINAL_NOT_FOUND);
                 }
             }
         }
+        Some(LauncherType::Tmux) => Ok(Launcher::Multiplexer(Multiplexer::Tmux)),
+        Some(LauncherType::Zellij) => Ok(Launcher::Multiplexer(Multiplexer::Zellij)),
+        Some(LauncherType::WezTerm) => Ok(Launcher::Multiplexer(Multiplexer::WezTerm)),
         None => {
             // lets try to create one using Neovide
-            if let Ok(term) = create_launcher(&PluginConfig {
-                launcher_type: Some(LauncherType::Neovide),
-                ..cfg.clone()
-            }) {
+            if let Ok(term) = create_launcher(
+                &PluginConfig {
+                    launcher_type: Some(LauncherType::Neovide),
+                    ..cfg.clone()
+                },
+                root_dir,
+            ) {
                 return Ok(term);
             }
 
             // if that doesnt work try again with terminal
-            if let Ok(term) = create_launcher(&PluginConfig {
-                launcher_type: Some(LauncherType::Terminal),
-                ..cfg.clone()
-            }) {
+            if let Ok(term) = create_launcher(
+                &PluginConfig {
+                    launcher_type: Some(LauncherType::Terminal),
+                    ..cfg.clone()
+                },
+                root_dir,
+            ) {
                 return Ok(term);
             }
 
@@ -157,36 +215,60 @@ fn create_launcher(cfg: &PluginConfig) -> Result<Terminal> {
     }
 }
 
-fn nvim_open_file_remote(nvim: &str, server: &str, file: &str, line: Option<usize>) -> Result<()> {
-    let remote_cmd = if let Some(line) = line {
-        format!("+{line} {file}")
-    } else {
-        file.to_string()
+/// Starts a new instance and waits for its server to come up, so that launches queued up
+/// behind the project lock can open their files in it instead of starting yet another one
+fn launch_and_wait(
+    launcher: &Launcher,
+    app: Application,
+    root_dir: &Path,
+    addr: &str,
+) -> Result<()> {
+    launcher.launch(app, root_dir)?;
+
+    if let Err(err) = rpc::wait_for_server(addr, SERVER_STARTUP_TIMEOUT) {
+        tracing::error!("Neovim server did not come up: {err:?}");
+        return Ok(());
+    }
+
+    let pid = match rpc::get_pid(addr) {
+        Ok(pid) => pid,
+        Err(err) => {
+            tracing::error!("Could not get pid of Neovim at {addr}: {err:?}");
+            return Ok(());
+        }
     };
 
-    tracing::debug!("Open '{remote_cmd}' via socket: {server}");
+    instances::register(&Instance {
+        pid,
+        address: addr.to_string(),
+        launcher: launcher.name(),
+        root_dir: root_dir.to_path_buf(),
+    })
+}
+
+/// Opens the target in the server at `addr`, false if there is no server to connect to. Any
+/// other error is returned, the server is alive and starting another one wouldn't help
+fn open_in_running(addr: &str, target: &Target) -> Result<bool> {
+    let mut client = match rpc::Client::connect(addr) {
+        Ok(client) => client,
+        Err(err) => {
+            tracing::error!("Failed to connect to neovim server: {err:?}");
+            return Ok(false);
+        }
+    };
 
-    let out = Command::new(nvim)
-        .arg("--server")
-        .arg(server)
-        .arg("--remote-send")
-        .arg(format!("\"<C-\\\\><C-n>:edit {remote_cmd}<CR>\""))
-        .output()?;
+    tracing::debug!("Open {target:?} via RPC: {addr}");
 
-    if !out.stderr.is_empty() {
-        bail!(String::from_utf8(out.stderr)?);
-    }
+    rpc::open_file(&mut client, target)?;
 
-    Ok(())
+    Ok(true)
 }
 
 fn run_fsock(
-    launcher: &Terminal,
+    launcher: &Launcher,
     app: Application,
-    nvim: &str,
     root_dir: &Path,
-    file: &str,
-    line: Option<usize>,
+    target: &Target,
 ) -> Result<()> {
     let socket_file = nvim_server::fsock_path(root_dir)?;
 
@@ -194,45 +276,31 @@ fn run_fsock(
 
     let mut app = app;
 
-    app.args = apply_vars(
-        &app.args,
-        VAR_ADDRESS,
-        socket_file
-            .to_str()
-            .context("could not convert socket file to string")?,
-    );
+    let addr = socket_file
+        .to_str()
+        .context("could not convert socket file to string")?;
+
+    app.args = apply_vars(&app.args, VAR_ADDRESS, addr);
 
     if socket_file.exists() {
         // if we couldnt communicate with the server despite existing apparently
         // delete it and start a new instance
-        if let Err(err) = nvim_open_file_remote(
-            nvim,
-            socket_file
-                .to_str()
-                .context("could not convert path to string")?,
-            file,
-            line,
-        ) {
-            tracing::error!("Failed to communicate with neovim server: {err:?}");
-
-            fs::remove_file(socket_file)?;
-            report_process_errors(app.launch_with(launcher)?)?;
+        if !open_in_running(addr, target)? {
+            fs::remove_file(&socket_file)?;
+            launch_and_wait(launcher, app, root_dir, addr)?;
         }
 
         return Ok(());
     }
 
-    report_process_errors(app.launch_with(launcher)?)?;
-    Ok(())
+    launch_and_wait(launcher, app, root_dir, addr)
 }
 
 fn run_netsock(
-    launcher: &Terminal,
+    launcher: &Launcher,
     mut app: Application,
-    nvim: &str,
     root_dir: &Path,
-    file: &str,
-    line: Option<usize>,
+    target: &Target,
 ) -> Result<()> {
     let port_file = nvim_server::netsock_port_file(root_dir)?;
     let port = nvim_server::read_or_allocate_netsock_port(root_dir)?;
@@ -244,14 +312,12 @@ fn run_netsock(
     if is_port_in_use(port) {
         // if we couldnt communicate with the server despite existing apparently
         // delete it and start a new instance
-        if let Err(err) = nvim_open_file_remote(nvim, &socket, file, line) {
-            tracing::error!("Failed to communicate with neovim server: {err:?}");
-
+        if !open_in_running(&socket, target)? {
             let socket = nvim_server::allocate_new_netsock_addr(root_dir)?;
             tracing::debug!("Trying to use netsock with port {socket}");
 
             app.args = apply_vars(&app.args, VAR_ADDRESS, &socket);
-            report_process_errors(app.launch_with(launcher)?)?;
+            launch_and_wait(launcher, app, root_dir, &socket)?;
         }
 
         return Ok(());
@@ -259,23 +325,21 @@ fn run_netsock(
 
     fs::write(port_file, port.to_string())?;
     app.args = apply_vars(&app.args, VAR_ADDRESS, &socket);
-    report_process_errors(app.launch_with(launcher)?)?;
-
-    Ok(())
+    launch_and_wait(launcher, app, root_dir, &socket)
 }
 
 pub fn run(
     plugin_config: &PluginConfig,
+    focus_settings: &FocusSettings,
     root_dir: PathBuf,
-    file: &Path,
-    line: Option<usize>,
+    target: &Target,
 ) -> Result<()> {
     let nvim = which("nvim")?
         .to_str()
         .context("could not convert nvim path to string")?
         .to_string();
 
-    let mut launcher = create_launcher(plugin_config)?;
+    let mut launcher = create_launcher(plugin_config, &root_dir)?;
 
     let mut app = Application::new(&nvim)
         .with_arg("--listen")
@@ -286,33 +350,23 @@ pub fn run(
 
     #[cfg(target_os = "linux")]
     {
-        let class = &classname(
-            root_dir
-                .to_str()
-                .context("could not convert path to string")?,
-        )?;
+        let class = &ProjectId::from_root(&root_dir)?.classname();
 
         app = app.with_class(class);
 
         // if is custom replace it in their args too
-        launcher = if let Terminal::Custom(_) = launcher {
-            apply_launcher_vars(&launcher, VAR_CLASSNAME, class)
-        } else {
-            launcher
-        };
+        launcher = apply_launcher_vars(&launcher, VAR_CLASSNAME, class);
     }
 
-    let file_str = file.to_str().context("could not convert path to string")?;
-
-    let mut app = if let Some(line) = line {
-        app.args = apply_vars(&app.args, VAR_LINE, &format!("+{line}"));
+    let mut app = if let Some(cursor_arg) = target.cursor_arg() {
+        app.args = apply_vars(&app.args, VAR_LINE, &cursor_arg);
         app
     } else {
         app.args.retain(|s| *s != VAR_LINE);
         app
     };
 
-    app.args = apply_vars(&app.args, VAR_FILE, file_str);
+    app.args = apply_vars(&app.args, VAR_FILE, &target.file);
 
     // due to Neovide having both a run argument with "--neovim-bin" and using the "--" separator
     // we kinda need to prepend this to the application
@@ -325,21 +379,113 @@ pub fn run(
             .insert("NVIM_APPNAME".to_string(), appname.clone());
     }
 
-    match plugin_config.socket_type {
-        Some(SocketType::Fsock) => run_fsock(&launcher, app, &nvim, &root_dir, file_str, line)?,
-        Some(SocketType::Netsock) => run_netsock(&launcher, app, &nvim, &root_dir, file_str, line)?,
+    // Defold fires one request per file when opening several at once, make sure only the first
+    // one starts an instance while the others wait for it and open their files in there
+    let lock = nvim_server::lock_launch(&root_dir)?;
+
+    if let Err(err) = instances::prune() {
+        tracing::error!("Could not prune stale instances: {err:?}");
+    }
+
+    let result = match plugin_config.socket_type {
+        Some(SocketType::Fsock) => run_fsock(&launcher, app, &root_dir, target),
+        Some(SocketType::Netsock) => run_netsock(&launcher, app, &root_dir, target),
         None => {
             if cfg!(target_os = "linux") || cfg!(target_os = "macos") {
-                run_fsock(&launcher, app, &nvim, &root_dir, file_str, line)?;
+                run_fsock(&launcher, app, &root_dir, target)
             } else {
-                run_netsock(&launcher, app, &nvim, &root_dir, file_str, line)?;
+                run_netsock(&launcher, app, &root_dir, target)
             }
         }
-    }
+    };
+
+    drop(lock);
 
-    if let Err(err) = focus_neovim(root_dir) {
+    // even if opening the file failed, e.g. because of unsaved changes, Neovim is where the
+    // user has to deal with it
+    if let Err(err) = focus_neovim(root_dir, focus_settings) {
         tracing::error!("Could not switch focus to neovim {err:?}");
     }
 
-    Ok(())
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        net::TcpListener,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use defold_nvim_core::nvim_server;
+    use pretty_assertions::assert_eq;
+    use rmpv::Value;
+
+    use crate::{launcher::open_in_running, rpc, target::Target};
+
+    /// Answers every request with an empty result, like Neovim does for `nvim_exec_lua`
+    fn serve_fake_neovim(listener: TcpListener) {
+        thread::spawn(move || {
+            for mut stream in listener.incoming().map_while(Result::ok) {
+                thread::spawn(move || {
+                    while let Ok(request) = rmpv::decode::read_value(&mut stream) {
+                        let id = request.as_array().unwrap()[1].clone();
+                        let response = Value::Array(vec![1.into(), id, Value::Nil, Value::Nil]);
+                        rmpv::encode::write_value(&mut stream, &response).unwrap();
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_concurrent_launches_start_one_server() {
+        let dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("concurrent-launch-{}", std::process::id()));
+        let root_dir = dir.join("game");
+        let runtime_root = dir.join("runtime");
+        fs::create_dir_all(&root_dir).unwrap();
+
+        let addr = format!("127.0.0.1:{}", nvim_server::find_free_port().unwrap());
+        let started = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for file in ["main.script", "player.script"] {
+                scope.spawn(|| {
+                    let lock = nvim_server::lock_launch_in(&runtime_root, &root_dir).unwrap();
+
+                    if !open_in_running(&addr, &Target::parse(file)).unwrap() {
+                        // starting Neovim takes a moment, the other launch has to wait for it
+                        thread::sleep(Duration::from_millis(200));
+                        serve_fake_neovim(TcpListener::bind(&addr).unwrap());
+                        started.fetch_add(1, Ordering::SeqCst);
+
+                        rpc::wait_for_server(&addr, Duration::from_secs(5)).unwrap();
+                    }
+
+                    drop(lock);
+                });
+            }
+        });
+
+        assert_eq!(1, started.load(Ordering::SeqCst));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_in_running_without_server() {
+        // bind and drop to get a port nothing listens on
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        assert!(!open_in_running(&addr, &Target::parse("main.script")).unwrap());
+    }
 }
@@ -3,20 +3,25 @@ use std::{
     path::{PathBuf, absolute},
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use clap::{Parser, Subcommand};
 use defold_nvim_core::{
-    editor,
-    focus::{focus_game, focus_neovim},
-    mobdap, neovide, path, project, script_api,
+    config, dependency_store, editor, editor_config,
+    focus::{FocusResult, focus_game, focus_neovim},
+    instances, offline, path, project, script_api, tools,
 };
 use tracing::Level;
 use tracing_appender::rolling::never;
 
-use crate::plugin_config::{LauncherType, PluginConfig, SocketType};
+use crate::{
+    plugin_config::{FocusArgs, LauncherType, PluginConfig, SocketType},
+    target::Target,
+};
 
 mod launcher;
 mod plugin_config;
+mod rpc;
+mod target;
 mod utils;
 
 #[derive(Parser, Debug)]
@@ -44,25 +49,38 @@ enum Commands {
         #[arg(long = "appname")]
         appname: Option<String>,
 
+        #[command(flatten)]
+        focus: FocusArgs,
+
         #[clap(value_name = "GAME_ROOT_DIR")]
         game_root_dir: String,
 
+        /// File to open, either just the path or as `file:line` or `file:line:col`
         #[clap(value_name = "FILE")]
         file: String,
 
         #[clap(value_name = "LINE")]
         line: Option<usize>,
 
+        #[clap(value_name = "COLUMN")]
+        column: Option<usize>,
+
         #[arg(last = true)]
         arguments: Option<Vec<String>>,
     },
     /// Focus the currently open instance of Neovim
     FocusNeovim {
+        #[command(flatten)]
+        focus: FocusArgs,
+
         #[clap(value_name = "GAME_ROOT_DIR", index = 1)]
         game_root_dir: String,
     },
     /// Focus the game
     FocusGame {
+        #[command(flatten)]
+        focus: FocusArgs,
+
         #[clap(value_name = "GAME_ROOT_DIR", index = 1)]
         game_root_dir: String,
     },
@@ -70,6 +88,21 @@ enum Commands {
     DownloadNeovide,
     /// Downloads Mobdap Debugger
     DownloadMobdap,
+    /// Restore the previously installed version of a managed tool
+    Rollback {
+        #[clap(value_name = "TOOL", index = 1)]
+        tool: String,
+    },
+    /// List, update or uninstall the tools defold.nvim downloads
+    Tools {
+        #[command(subcommand)]
+        cmd: ToolsCommands,
+    },
+    /// Show whether offline mode is enabled and what doesn't work because of it
+    Offline {
+        #[clap(value_name = "GAME_ROOT_DIR", index = 1)]
+        game_root_dir: Option<String>,
+    },
     /// Install dependencies for game
     InstallDependencies {
         #[clap(long = "force-redownload")]
@@ -78,6 +111,8 @@ enum Commands {
         #[clap(value_name = "GAME_ROOT_DIR", index = 1)]
         game_root_dir: String,
     },
+    /// Remove downloaded dependencies no project uses anymore
+    GcDependencies,
     /// List dependencies of game
     ListDependencies {
         #[clap(value_name = "GAME_ROOT_DIR", index = 1)]
@@ -96,6 +131,16 @@ enum Commands {
         #[clap(value_name = "COMMAND", index = 2)]
         command: String,
     },
+    /// List or kill running Neovim instances
+    Instances {
+        #[command(subcommand)]
+        cmd: InstancesCommands,
+    },
+    /// Restore the code editor preferences Defold had before defold.nvim was set as default editor
+    UnsetDefaultEditor {
+        #[clap(value_name = "GAME_ROOT_DIR", index = 1)]
+        game_root_dir: String,
+    },
     /// Compile `.script_api` file and return the resulting `.lua` in stdout
     CompileScriptApi {
         #[clap(value_name = "SCRIPT_API_FILE", index = 1)]
@@ -105,6 +150,59 @@ enum Commands {
     Version,
 }
 
+fn print_focus_result(result: &FocusResult) {
+    match &result.backend {
+        None => println!("No focus switcher found"),
+        Some(backend) if result.window_found => println!("Focused window using {backend}"),
+        Some(backend) if result.stderr.is_empty() => println!("{backend} found no window"),
+        Some(backend) => println!("{backend} found no window: {}", result.stderr),
+    }
+}
+
+/// Explicitly passed line and column take precedence over those parsed from `file`
+fn target(file: &str, line: Option<usize>, column: Option<usize>) -> Result<Target> {
+    let mut target = Target::parse(file);
+
+    if line.is_some() {
+        target.line = line;
+        target.column = column;
+    }
+
+    target.file = absolute(&target.file)?
+        .to_str()
+        .context("could not convert path to string")?
+        .to_string();
+
+    Ok(target)
+}
+
+#[derive(Subcommand, Debug)]
+enum ToolsCommands {
+    /// List the known tools with their installed and latest versions
+    List,
+    /// Update a tool, or every installed tool if none is given
+    Update {
+        #[clap(value_name = "TOOL", index = 1)]
+        tool: Option<String>,
+    },
+    /// Remove a tool and its version files
+    Uninstall {
+        #[clap(value_name = "TOOL", index = 1)]
+        tool: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum InstancesCommands {
+    /// List all running Neovim instances
+    List,
+    /// Kill the Neovim instance of a project
+    Kill {
+        #[clap(value_name = "PROJECT_ID", index = 1)]
+        project_id: String,
+    },
+}
+
 fn main() -> Result<()> {
     let mut err = None;
 
@@ -116,6 +214,25 @@ fn main() -> Result<()> {
         }
     };
 
+    // launcher settings can enable debug logging too, so load them before setting up the logger
+    let config = match &args {
+        Some(Args {
+            cmd: Commands::LaunchNeovim { game_root_dir, .. },
+            ..
+        }) => Some(
+            absolute(game_root_dir)
+                .map_err(anyhow::Error::from)
+                .and_then(|root_dir| config::load(Some(&root_dir))),
+        ),
+        _ => None,
+    };
+
+    let debug_config = config
+        .as_ref()
+        .and_then(|config| config.as_ref().ok())
+        .and_then(|config| config.launcher.debug)
+        .unwrap_or_default();
+
     let logs = path::cache_dir()?.join("logs");
 
     fs::create_dir_all(&logs)?;
@@ -126,7 +243,7 @@ fn main() -> Result<()> {
         .with_file(true)
         .with_line_number(true)
         .with_max_level(match &args {
-            Some(args) if args.debug => Level::DEBUG,
+            Some(args) if args.debug || debug_config => Level::DEBUG,
             Some(_) => Level::INFO,
             None => Level::DEBUG,
         })
@@ -143,6 +260,15 @@ fn main() -> Result<()> {
         err.exit();
     }
 
+    let config = match config {
+        Some(Ok(config)) => config,
+        Some(Err(err)) => {
+            tracing::error!("Could not load config, using defaults: {err:?}");
+            config::Config::default()
+        }
+        None => config::Config::default(),
+    };
+
     let args = args.unwrap();
 
     match args.cmd {
@@ -151,10 +277,12 @@ fn main() -> Result<()> {
             socket_type,
             executable,
             appname,
+            focus,
             arguments,
             game_root_dir,
             file,
             line,
+            column,
         } => launcher::run(
             &PluginConfig {
                 launcher_type,
@@ -162,21 +290,94 @@ fn main() -> Result<()> {
                 executable,
                 appname,
                 arguments,
-            },
+            }
+            .or_settings(config.launcher),
+            &focus.into(),
             absolute(game_root_dir)?,
-            &absolute(file)?,
-            line,
+            &target(&file, line, column)?,
         )?,
-        Commands::FocusNeovim { game_root_dir } => focus_neovim(absolute(game_root_dir)?)?,
-        Commands::FocusGame { game_root_dir } => focus_game(absolute(game_root_dir)?)?,
+        Commands::FocusNeovim {
+            focus,
+            game_root_dir,
+        } => {
+            print_focus_result(&focus_neovim(absolute(game_root_dir)?, &focus.into())?);
+        }
+        Commands::FocusGame {
+            focus,
+            game_root_dir,
+        } => {
+            print_focus_result(&focus_game(absolute(game_root_dir)?, &focus.into())?);
+        }
         Commands::DownloadNeovide => {
-            let path = neovide::install()?;
+            let path = tools::install("neovide", None)?;
             println!("Installed neovide at {}", path.display());
         }
         Commands::DownloadMobdap => {
-            let path = mobdap::install()?;
+            let path = tools::install("mobdap", None)?;
             println!("Installed mobdap at {}", path.display());
         }
+        Commands::Rollback { tool } => {
+            let version = tools::rollback(&tool)?;
+            println!("Rolled back {tool} to {version}, pin it to keep it from updating again");
+        }
+        Commands::Tools { cmd } => match cmd {
+            ToolsCommands::List => {
+                for tool in tools::list()? {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        tool.name,
+                        tool.installed_version.as_deref().unwrap_or("-"),
+                        tool.latest_version.as_deref().unwrap_or("-"),
+                        tool.path.display(),
+                        tool.size.map_or("-".to_string(), utils::format_size),
+                    );
+                }
+            }
+            ToolsCommands::Update { tool } => {
+                let names = match tool {
+                    Some(tool) => vec![tool],
+                    None => tools::list()?
+                        .into_iter()
+                        .filter(|tool| tool.size.is_some())
+                        .map(|tool| tool.name)
+                        .collect(),
+                };
+
+                let mut failed = Vec::new();
+
+                for name in names {
+                    match tools::update(&name, None) {
+                        Ok(Some(version)) => println!("Updated {name} to {version}"),
+                        Ok(None) => println!("{name} is up to date"),
+                        Err(err) => {
+                            println!("Could not update {name}: {err}");
+                            failed.push(name);
+                        }
+                    }
+                }
+
+                if !failed.is_empty() {
+                    bail!("Could not update {}", failed.join(", "));
+                }
+            }
+            ToolsCommands::Uninstall { tool } => {
+                tools::uninstall(&tool)?;
+                println!("Uninstalled {tool}");
+            }
+        },
+        Commands::Offline { game_root_dir } => {
+            if offline::is_enabled() {
+                println!("Offline mode is enabled");
+
+                let root_dir = game_root_dir.map(absolute).transpose()?;
+
+                for feature in offline::degraded_features(root_dir.as_deref())? {
+                    println!("- {feature}");
+                }
+            } else {
+                println!("Offline mode is disabled");
+            }
+        }
         Commands::InstallDependencies {
             force_redownload,
             game_root_dir,
@@ -186,6 +387,10 @@ fn main() -> Result<()> {
             project::install_dependencies(&root_dir, force_redownload)?;
             println!("Finished installing dependencies for {game_root_dir}",);
         }
+        Commands::GcDependencies => {
+            let removed = dependency_store::collect_garbage(true)?;
+            println!("Removed {removed} unused dependencies");
+        }
         Commands::ListDependencies { game_root_dir } => {
             let root_dir = absolute(&game_root_dir)?;
 
@@ -208,6 +413,37 @@ fn main() -> Result<()> {
                 editor::send_command(port, &command)?;
             }
         }
+        Commands::Instances { cmd } => match cmd {
+            InstancesCommands::List => {
+                for (project_id, instance) in instances::list()? {
+                    println!(
+                        "{project_id}\t{}\t{}\t{}\t{}",
+                        instance.pid,
+                        instance.launcher,
+                        instance.address,
+                        instance.root_dir.display()
+                    );
+                }
+            }
+            InstancesCommands::Kill { project_id } => {
+                let instance = instances::kill(&project_id)?;
+                println!(
+                    "Killed Neovim ({}) for {}",
+                    instance.pid,
+                    instance.root_dir.display()
+                );
+            }
+        },
+        Commands::UnsetDefaultEditor { game_root_dir } => {
+            let root_dir = absolute(game_root_dir)?;
+
+            if let Some(port) = editor::find_port(&root_dir) {
+                editor_config::unset_default_editor(port, &root_dir)?;
+                println!("Restored previous editor settings");
+            } else {
+                println!("Could not find editor port, is the editor open?");
+            }
+        }
         Commands::CompileScriptApi { input } => {
             if !input.exists() {
                 println!("File {} could not be found", input.display());
@@ -226,3 +462,206 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{env, fs, os::unix::fs::PermissionsExt, process::Command};
+
+    use clap::Parser;
+    use defold_nvim_core::{
+        editor_config::{self, LauncherSettings, SocketType},
+        focus::{FocusSettings, Switcher},
+    };
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        Args, Commands,
+        plugin_config::{LauncherType, PluginConfig},
+    };
+
+    #[test]
+    fn test_runner_script_round_trip() {
+        let dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("round trip 'quoted' $dir {}", std::process::id()));
+        let project_dir = dir.join("my game");
+        fs::create_dir_all(&project_dir).unwrap();
+        let project_dir = project_dir.canonicalize().unwrap();
+
+        // stands in for the bridge, prints every argument it receives NUL separated
+        let fake_bridge = dir.join("fake bridge");
+        fs::write(
+            &fake_bridge,
+            "#!/bin/sh\nfor arg in \"$@\"; do printf '%s\\0' \"$arg\"; done\n",
+        )
+        .unwrap();
+        fs::set_permissions(&fake_bridge, fs::Permissions::from_mode(0o700)).unwrap();
+
+        let arguments = vec![
+            "--title".to_string(),
+            "it's $HOME `id` \"quoted\"".to_string(),
+            r"back\slash\".to_string(),
+            "semi; colon & amp | pipe".to_string(),
+            String::new(),
+        ];
+
+        let settings = LauncherSettings {
+            launcher_type: Some(editor_config::LauncherType::Terminal),
+            executable: Some("/opt/my apps/kitty's \"term\"".to_string()),
+            socket_type: Some(SocketType::Netsock),
+            arguments: Some(arguments.clone()),
+            appname: Some("nvim-$APPNAME".to_string()),
+            debug: Some(true),
+        };
+
+        let focus_command = vec![
+            "my-focus".to_string(),
+            "--class".to_string(),
+            "{class} 'x'".to_string(),
+        ];
+
+        let focus_settings = FocusSettings {
+            switcher: None,
+            command: Some(focus_command.clone()),
+        };
+
+        let script = dir.join("run.sh");
+        fs::write(
+            &script,
+            editor_config::render_runner_script(&fake_bridge, &settings, &focus_settings).unwrap(),
+        )
+        .unwrap();
+
+        let out = Command::new("bash")
+            .arg(&script)
+            .arg("/my game/main/it's $x.script")
+            .arg("12")
+            .current_dir(&project_dir)
+            .output()
+            .unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(out.status.success(), "{out:?}");
+
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        let cli = std::iter::once("defold-nvim-bridge")
+            .chain(stdout.strip_suffix('\0').unwrap().split('\0'))
+            .collect::<Vec<_>>();
+
+        let args = Args::try_parse_from(cli).unwrap();
+
+        assert!(args.debug);
+
+        let Commands::LaunchNeovim {
+            launcher_type,
+            executable,
+            appname,
+            game_root_dir,
+            file,
+            line,
+            focus,
+            arguments: parsed_arguments,
+            ..
+        } = args.cmd
+        else {
+            panic!("expected launch-neovim, got {:?}", args.cmd);
+        };
+
+        assert!(matches!(launcher_type, Some(LauncherType::Terminal)));
+        assert_eq!(
+            Some("/opt/my apps/kitty's \"term\"".to_string()),
+            executable
+        );
+        assert_eq!(Some("nvim-$APPNAME".to_string()), appname);
+        assert_eq!(project_dir.to_str(), Some(game_root_dir.as_str()));
+        assert_eq!("/my game/main/it's $x.script", file);
+        assert_eq!(Some(12), line);
+        assert_eq!(Some(arguments), parsed_arguments);
+        assert!(focus.switcher.is_none());
+        assert_eq!(focus_command, focus.command);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_focus_args() {
+        let args = Args::try_parse_from([
+            "defold-nvim-bridge",
+            "focus-game",
+            "--focus-switcher",
+            "i3-msg",
+            "/my game",
+        ])
+        .unwrap();
+
+        let Commands::FocusGame {
+            focus,
+            game_root_dir,
+        } = args.cmd
+        else {
+            panic!("expected focus-game, got {:?}", args.cmd);
+        };
+
+        assert_eq!("/my game", game_root_dir);
+
+        let settings = FocusSettings::from(focus);
+
+        assert_eq!(Some(Switcher::I3Msg), settings.switcher);
+        assert!(settings.command.is_none());
+    }
+
+    #[test]
+    fn test_cli_args_take_precedence_over_config() {
+        let args = Args::try_parse_from([
+            "defold-nvim-bridge",
+            "launch-neovim",
+            "--executable",
+            "/opt/my apps/kitty's \"term\"",
+            "/my game",
+            "main.script",
+            "--",
+            "it's $HOME `id`",
+            "",
+        ])
+        .unwrap();
+
+        let Commands::LaunchNeovim {
+            launcher_type,
+            socket_type,
+            executable,
+            appname,
+            arguments,
+            ..
+        } = args.cmd
+        else {
+            panic!("expected launch-neovim, got {:?}", args.cmd);
+        };
+
+        let cfg = PluginConfig {
+            launcher_type,
+            socket_type,
+            executable,
+            appname,
+            arguments,
+        }
+        .or_settings(LauncherSettings {
+            launcher_type: Some(editor_config::LauncherType::Terminal),
+            executable: Some("kitty".to_string()),
+            appname: Some("nvim-defold".to_string()),
+            arguments: Some(vec!["--single-instance".to_string()]),
+            ..Default::default()
+        });
+
+        assert!(matches!(cfg.launcher_type, Some(LauncherType::Terminal)));
+        assert!(cfg.socket_type.is_none());
+        assert_eq!(
+            Some("/opt/my apps/kitty's \"term\"".to_string()),
+            cfg.executable
+        );
+        assert_eq!(Some("nvim-defold".to_string()), cfg.appname);
+        assert_eq!(
+            Some(vec!["it's $HOME `id`".to_string(), String::new()]),
+            cfg.arguments
+        );
+    }
+}
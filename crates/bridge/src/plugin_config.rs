@@ -1,3 +1,8 @@
+use defold_nvim_core::{
+    editor_config::{self, LauncherSettings},
+    focus::{FocusSettings, Switcher},
+};
+
 #[derive(Debug, clap::ValueEnum, Clone, Copy)]
 pub enum LauncherType {
     #[clap(name = "neovide")]
@@ -5,6 +10,15 @@ pub enum LauncherType {
 
     #[clap(name = "terminal")]
     Terminal,
+
+    #[clap(name = "tmux")]
+    Tmux,
+
+    #[clap(name = "zellij")]
+    Zellij,
+
+    #[clap(name = "wezterm")]
+    WezTerm,
 }
 
 #[derive(Debug, clap::ValueEnum, Clone, Copy)]
@@ -24,3 +38,59 @@ pub struct PluginConfig {
     pub appname: Option<String>,
     pub arguments: Option<Vec<String>>,
 }
+
+impl From<editor_config::LauncherType> for LauncherType {
+    fn from(value: editor_config::LauncherType) -> Self {
+        match value {
+            editor_config::LauncherType::Neovide => LauncherType::Neovide,
+            editor_config::LauncherType::Terminal => LauncherType::Terminal,
+            editor_config::LauncherType::Tmux => LauncherType::Tmux,
+            editor_config::LauncherType::Zellij => LauncherType::Zellij,
+            editor_config::LauncherType::WezTerm => LauncherType::WezTerm,
+        }
+    }
+}
+
+impl From<editor_config::SocketType> for SocketType {
+    fn from(value: editor_config::SocketType) -> Self {
+        match value {
+            editor_config::SocketType::Fsock => SocketType::Fsock,
+            editor_config::SocketType::Netsock => SocketType::Netsock,
+        }
+    }
+}
+
+impl PluginConfig {
+    /// Fills every setting that wasn't passed via CLI from the launcher config
+    #[must_use]
+    pub fn or_settings(self, settings: LauncherSettings) -> Self {
+        Self {
+            launcher_type: self
+                .launcher_type
+                .or(settings.launcher_type.map(Into::into)),
+            socket_type: self.socket_type.or(settings.socket_type.map(Into::into)),
+            executable: self.executable.or(settings.executable),
+            appname: self.appname.or(settings.appname),
+            arguments: self.arguments.or(settings.arguments),
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct FocusArgs {
+    #[arg(long = "focus-switcher")]
+    pub switcher: Option<Switcher>,
+
+    /// Custom focus command, passed once per argument
+    #[arg(long = "focus-command", allow_hyphen_values = true)]
+    pub command: Vec<String>,
+}
+
+impl From<FocusArgs> for FocusSettings {
+    fn from(value: FocusArgs) -> Self {
+        Self {
+            switcher: value.switcher,
+            command: (!value.command.is_empty()).then_some(value.command),
+        }
+    }
+}
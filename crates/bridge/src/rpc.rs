@@ -0,0 +1,250 @@
+use anyhow::{Context, Result};
+use rmpv::Value;
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::target::Target;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const MSG_REQUEST: u64 = 0;
+const MSG_RESPONSE: u64 = 1;
+
+const OPEN_FILE_LUA: &str = r#"
+local file, line, column = ...
+
+-- leave insert/terminal mode so we don't end up typing into a buffer or terminal
+local mode = vim.api.nvim_get_mode().mode
+if mode:sub(1, 1) == "i" or mode:sub(1, 1) == "t" then
+    vim.cmd.stopinsert()
+end
+
+vim.cmd.edit(vim.fn.fnameescape(file))
+
+if line > 0 then
+    local last_line = vim.api.nvim_buf_line_count(0)
+    vim.api.nvim_win_set_cursor(0, { math.min(line, last_line), math.max(column - 1, 0) })
+end
+"#;
+
+/// Error Neovim answered a request with, as opposed to not being able to talk to it at all
+#[derive(Debug)]
+pub struct NeovimError(String);
+
+impl fmt::Display for NeovimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Neovim: {}", self.0)
+    }
+}
+
+impl std::error::Error for NeovimError {}
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+/// Minimal msgpack-RPC client for talking to a Neovim server
+pub struct Client {
+    stream: Box<dyn Stream>,
+    next_id: u32,
+}
+
+impl Client {
+    pub fn connect(addr: &str) -> Result<Self> {
+        let stream: Box<dyn Stream> = if let Ok(socket_addr) = addr.parse::<SocketAddr>() {
+            let stream = TcpStream::connect_timeout(&socket_addr, TIMEOUT)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            Box::new(stream)
+        } else {
+            connect_local(addr)?
+        };
+
+        Ok(Self { stream, next_id: 0 })
+    }
+
+    pub fn call(&mut self, method: &str, params: Vec<Value>) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = Value::Array(vec![
+            MSG_REQUEST.into(),
+            id.into(),
+            method.into(),
+            Value::Array(params),
+        ]);
+
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, &request)?;
+        self.stream.write_all(&buf)?;
+        self.stream.flush()?;
+
+        loop {
+            let message = rmpv::decode::read_value(&mut self.stream)
+                .context("could not read response from Neovim")?;
+
+            match message.as_array().map(Vec::as_slice) {
+                Some([kind, msg_id, error, result])
+                    if kind.as_u64() == Some(MSG_RESPONSE)
+                        && msg_id.as_u64() == Some(u64::from(id)) =>
+                {
+                    if !error.is_nil() {
+                        return Err(NeovimError(error_message(error)).into());
+                    }
+
+                    return Ok(result.clone());
+                }
+                _ => tracing::debug!("Ignoring unrelated RPC message {message}"),
+            }
+        }
+    }
+
+    pub fn exec_lua(&mut self, code: &str, args: Vec<Value>) -> Result<Value> {
+        self.call("nvim_exec_lua", vec![code.into(), Value::Array(args)])
+    }
+}
+
+fn error_message(error: &Value) -> String {
+    // errors are sent as [type, message]
+    match error.as_array().map(Vec::as_slice) {
+        Some([_, Value::String(message)]) => message.as_str().unwrap_or_default().to_string(),
+        _ => error.to_string(),
+    }
+}
+
+#[cfg(unix)]
+fn connect_local(path: &str) -> Result<Box<dyn Stream>> {
+    use std::os::unix::net::UnixStream;
+
+    let stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    Ok(Box::new(stream))
+}
+
+#[cfg(windows)]
+fn connect_local(path: &str) -> Result<Box<dyn Stream>> {
+    // named pipes can be opened like regular files
+    let pipe = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+
+    Ok(Box::new(pipe))
+}
+
+pub fn get_pid(server: &str) -> Result<u32> {
+    let mut client = Client::connect(server)?;
+
+    let pid = client.call(
+        "nvim_call_function",
+        vec!["getpid".into(), Value::Array(vec![])],
+    )?;
+
+    pid.as_u64()
+        .and_then(|pid| u32::try_from(pid).ok())
+        .context("getpid did not return a pid")
+}
+
+/// Waits until a Neovim server accepts connections at `server`
+pub fn wait_for_server(server: &str, timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+
+    loop {
+        match Client::connect(server) {
+            Ok(_) => {
+                tracing::debug!(
+                    "Neovim server at {server} is up after {:?}",
+                    start.elapsed()
+                );
+                return Ok(());
+            }
+            Err(err) if start.elapsed() > timeout => {
+                return Err(err.context(format!("timed out waiting for {server}")));
+            }
+            Err(_) => thread::sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+pub fn open_file(client: &mut Client, target: &Target) -> Result<()> {
+    client.exec_lua(
+        OPEN_FILE_LUA,
+        vec![
+            target.file.as_str().into(),
+            (target.line.unwrap_or_default() as u64).into(),
+            (target.column.unwrap_or_default() as u64).into(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use pretty_assertions::assert_eq;
+    use rmpv::Value;
+
+    use crate::{
+        rpc::{Client, NeovimError, open_file},
+        target::Target,
+    };
+
+    #[test]
+    fn test_open_file_reports_neovim_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let request = rmpv::decode::read_value(&mut stream).unwrap();
+            let request = request.as_array().unwrap().clone();
+
+            // notifications can arrive before the response and need to be skipped
+            let notification = Value::Array(vec![
+                2.into(),
+                "nvim_buf_lines_event".into(),
+                Value::Array(vec![]),
+            ]);
+            let response = Value::Array(vec![
+                1.into(),
+                request[1].clone(),
+                Value::Array(vec![0.into(), "E37: No write since last change".into()]),
+                Value::Nil,
+            ]);
+
+            rmpv::encode::write_value(&mut stream, &notification).unwrap();
+            rmpv::encode::write_value(&mut stream, &response).unwrap();
+
+            request
+        });
+
+        let err = open_file(
+            &mut Client::connect(&addr).unwrap(),
+            &Target::parse("/my game/100% main.script:12:5"),
+        )
+        .unwrap_err();
+        let request = server.join().unwrap();
+
+        assert_eq!("Neovim: E37: No write since last change", err.to_string());
+        assert!(err.is::<NeovimError>());
+        assert_eq!(Some("nvim_exec_lua"), request[2].as_str());
+        assert_eq!(
+            &Value::Array(vec![
+                "/my game/100% main.script".into(),
+                12.into(),
+                5.into()
+            ]),
+            &request[3].as_array().unwrap()[1]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::path::Path;
+
+/// A file to open with an optional cursor position, both line and column are 1-based
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub file: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl Target {
+    /// Parses `file`, `file:line` and `file:line:col` style targets, a file
+    /// that exists exactly as given is never split up
+    #[must_use]
+    pub fn parse(target: &str) -> Self {
+        let plain = Self {
+            file: target.to_string(),
+            line: None,
+            column: None,
+        };
+
+        if Path::new(target).exists() {
+            return plain;
+        }
+
+        let Some((rest, last)) = split_number(target) else {
+            return plain;
+        };
+
+        match split_number(rest) {
+            Some((file, line)) if !file.is_empty() => Self {
+                file: file.to_string(),
+                line: Some(line),
+                column: Some(last),
+            },
+            _ if !rest.is_empty() => Self {
+                file: rest.to_string(),
+                line: Some(last),
+                column: None,
+            },
+            _ => plain,
+        }
+    }
+
+    /// Neovim command line argument that moves the cursor to the target position
+    #[must_use]
+    pub fn cursor_arg(&self) -> Option<String> {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => Some(format!("+call cursor({line}, {column})")),
+            (Some(line), None) => Some(format!("+{line}")),
+            _ => None,
+        }
+    }
+}
+
+fn split_number(s: &str) -> Option<(&str, usize)> {
+    let (rest, number) = s.rsplit_once(':')?;
+
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some((rest, number.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::target::Target;
+    use pretty_assertions::assert_eq;
+
+    fn target(file: &str, line: Option<usize>, column: Option<usize>) -> Target {
+        Target {
+            file: file.to_string(),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            target("/game/main.script", None, None),
+            Target::parse("/game/main.script")
+        );
+        assert_eq!(
+            target("/game/main.script", Some(12), None),
+            Target::parse("/game/main.script:12")
+        );
+        assert_eq!(
+            target("/game/main.script", Some(12), Some(5)),
+            Target::parse("/game/main.script:12:5")
+        );
+        assert_eq!(
+            target(r"C:\game\main.script", Some(12), Some(5)),
+            Target::parse(r"C:\game\main.script:12:5")
+        );
+        assert_eq!(
+            target("/game/a:b.script", Some(3), None),
+            Target::parse("/game/a:b.script:3")
+        );
+        assert_eq!(target(":12", None, None), Target::parse(":12"));
+    }
+
+    #[test]
+    fn test_cursor_arg() {
+        assert_eq!(None, target("a", None, None).cursor_arg());
+        assert_eq!(
+            Some("+12".to_string()),
+            target("a", Some(12), None).cursor_arg()
+        );
+        assert_eq!(
+            Some("+call cursor(12, 5)".to_string()),
+            target("a", Some(12), Some(5)).cursor_arg()
+        );
+    }
+}
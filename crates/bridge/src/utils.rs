@@ -21,3 +21,41 @@ pub fn is_port_in_use(port: u16) -> bool {
     }
     false
 }
+
+/// Formats a size in bytes for humans, e.g. `12.3 MB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = UNITS[0];
+
+    for next in &UNITS[1..] {
+        if size < 1000.0 {
+            break;
+        }
+
+        size /= 1000.0;
+        unit = next;
+    }
+
+    format!("{size:.1} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::format_size;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!("512 B", format_size(512));
+        assert_eq!("1.5 KB", format_size(1500));
+        assert_eq!("12.3 MB", format_size(12_345_678));
+        assert_eq!("2.0 GB", format_size(2_000_000_000));
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
+
+use crate::{
+    dependency_auth::HostAuth, dependency_override::DependencyOverride,
+    editor_config::LauncherSettings, focus::FocusSettings, github::GithubSettings, path,
+    tools::ToolSpec,
+};
+
+const PROJECT_CONFIG_NAME: &str = ".defold.nvim.toml";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub launcher: LauncherSettings,
+
+    #[serde(default)]
+    pub focus: FocusSettings,
+
+    #[serde(default)]
+    pub github: GithubSettings,
+
+    /// Credentials for private dependencies, by host
+    #[serde(default)]
+    pub dependency_auth: BTreeMap<String, HostAuth>,
+
+    /// Local checkouts or git repositories used instead of downloading a dependency, by dependency url
+    #[serde(default)]
+    pub dependency_overrides: BTreeMap<String, DependencyOverride>,
+
+    /// Never download anything, see [`crate::offline`]
+    #[serde(default)]
+    pub offline: bool,
+
+    /// Release tags tools are pinned to, by tool name
+    #[serde(default)]
+    pub pins: HashMap<String, String>,
+
+    /// Tools to add to or replace in the tool manifest, only read from the global config
+    #[serde(default)]
+    pub tools: BTreeMap<String, ToolSpec>,
+}
+
+pub fn global_path() -> Result<PathBuf> {
+    let dir = path::data_dir()?;
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir.join("config.toml"))
+}
+
+#[must_use]
+pub fn project_path(root_dir: &Path) -> PathBuf {
+    root_dir.join(PROJECT_CONFIG_NAME)
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    if !path.exists() {
+        return Ok(Table::new());
+    }
+
+    fs::read_to_string(path)?
+        .parse::<Table>()
+        .with_context(|| format!("could not parse config file {}", path.display()))
+}
+
+fn merge(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overrides)) => merge(base, overrides),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Loads the global config with the project config (if any) applied on top of it
+pub fn load(root_dir: Option<&Path>) -> Result<Config> {
+    let mut table = read_table(&global_path()?)?;
+
+    if let Some(root_dir) = root_dir {
+        merge(&mut table, read_table(&project_path(root_dir))?);
+    }
+
+    // the table itself isn't logged, it may contain tokens
+    tracing::debug!(
+        "Loaded config sections {:?}",
+        table.keys().collect::<Vec<_>>()
+    );
+
+    Ok(table.try_into()?)
+}
+
+/// Only read from the project config, sharing one id between all projects makes no sense
+pub fn project_id_override(root_dir: &Path) -> Result<Option<String>> {
+    let table = read_table(&project_path(root_dir))?;
+
+    Ok(table
+        .get("project")
+        .and_then(|project| project.get("id"))
+        .and_then(Value::as_str)
+        .map(ToString::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Config, merge};
+    use crate::editor_config::LauncherType;
+    use pretty_assertions::assert_eq;
+    use toml::Table;
+
+    #[test]
+    fn test_project_overrides_global() {
+        let mut global: Table = r#"
+[launcher]
+type = "neovide"
+appname = "nvim-defold"
+arguments = ["--frame", "none"]
+"#
+        .parse()
+        .unwrap();
+
+        let project: Table = r#"
+[launcher]
+type = "terminal"
+executable = "kitty"
+"#
+        .parse()
+        .unwrap();
+
+        merge(&mut global, project);
+
+        let config: Config = global.try_into().unwrap();
+
+        assert!(matches!(
+            config.launcher.launcher_type,
+            Some(LauncherType::Terminal)
+        ));
+        assert_eq!(Some("kitty".to_string()), config.launcher.executable);
+        assert_eq!(Some("nvim-defold".to_string()), config.launcher.appname);
+        assert_eq!(
+            Some(vec!["--frame".to_string(), "none".to_string()]),
+            config.launcher.arguments
+        );
+    }
+
+    #[test]
+    fn test_project_pins_override_global() {
+        let mut global: Table = r#"
+[pins]
+mobdap = "v0.1.4"
+neovide = "0.15.2"
+"#
+        .parse()
+        .unwrap();
+
+        let project: Table = r#"
+[pins]
+mobdap = "v0.1.5"
+"#
+        .parse()
+        .unwrap();
+
+        merge(&mut global, project);
+
+        let config: Config = global.try_into().unwrap();
+
+        assert_eq!(Some(&"v0.1.5".to_string()), config.pins.get("mobdap"));
+        assert_eq!(Some(&"0.15.2".to_string()), config.pins.get("neovide"));
+    }
+}
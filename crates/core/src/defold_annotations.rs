@@ -1,6 +1,6 @@
 use std::{
     fs::{self, File},
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
@@ -8,8 +8,10 @@ use anyhow::{Context, Result, bail};
 use version_compare::Version;
 use zip::ZipArchive;
 
-use crate::{github, path, project, utils};
+use crate::{github, offline, project, release_downloader, utils};
 
+/// Not an executable, but named like one to share pins and meta files with the other tools
+const EXECUTABLE_NAME: &str = "defold_annotations";
 const OWNER: &str = "astrochili";
 const REPOSITORY: &str = "defold-annotations";
 
@@ -19,11 +21,7 @@ pub fn dir() -> Result<PathBuf> {
 }
 
 fn version_path() -> Result<PathBuf> {
-    let dir = path::data_dir()?.join("meta");
-
-    fs::create_dir_all(&dir)?;
-
-    Ok(dir.join("defold_annotations_version"))
+    release_downloader::version_path(EXECUTABLE_NAME)
 }
 
 fn version() -> Result<String> {
@@ -36,13 +34,17 @@ fn version() -> Result<String> {
     Ok(fs::read_to_string(file)?)
 }
 
-fn is_update_available() -> Result<bool> {
+fn is_update_available(pin: Option<&str>) -> Result<bool> {
     let defold_dir = dir()?;
 
     if !defold_dir.exists() {
         return Ok(true);
     }
 
+    if let Some(pin) = pin {
+        return Ok(version().map_or(true, |v| v != pin));
+    }
+
     if version_path()?.exists() {
         // if the version file is younger than a week dont bother
         let last_modified = version_path()?.metadata()?.modified()?;
@@ -64,7 +66,7 @@ fn is_update_available() -> Result<bool> {
         return Ok(true);
     };
 
-    let release = github::fetch_release(OWNER, REPOSITORY)?;
+    let release = github::fetch_release(OWNER, REPOSITORY, None)?;
 
     tracing::debug!("Defold Annotations Version {} is newest", release.tag_name);
 
@@ -75,8 +77,19 @@ fn is_update_available() -> Result<bool> {
     Ok(current > installed)
 }
 
-pub fn install() -> Result<()> {
-    if !is_update_available()? {
+/// Installs the annotations, respecting the version pinned for `root_dir` if any
+pub fn install(root_dir: Option<&Path>) -> Result<()> {
+    if offline::is_enabled() {
+        if !dir()?.exists() {
+            tracing::warn!("Defold annotations are not installed and offline mode is enabled");
+        }
+
+        return Ok(());
+    }
+
+    let pin = release_downloader::pinned_version(EXECUTABLE_NAME, root_dir)?;
+
+    if !is_update_available(pin.as_deref())? {
         return Ok(());
     }
 
@@ -87,9 +100,10 @@ pub fn install() -> Result<()> {
         fs::remove_dir_all(&defold_dir)?;
     }
 
-    let (download_path, release) = github::download_release_matching(OWNER, REPOSITORY, |asset| {
-        asset.name.starts_with("defold_api_")
-    })?;
+    let (download_path, release, verified_digest) =
+        github::download_release_matching(OWNER, REPOSITORY, pin.as_deref(), |asset| {
+            asset.name.starts_with("defold_api_")
+        })?;
 
     let parent_dir = download_path.parent().context("could not get parent dir")?;
 
@@ -112,6 +126,13 @@ pub fn install() -> Result<()> {
     utils::move_file(&defold_api_dir, &defold_dir)?;
     fs::write(version_path()?, release.tag_name)?;
 
+    let digest_path = release_downloader::digest_path(EXECUTABLE_NAME)?;
+    match verified_digest {
+        Some(digest) => fs::write(digest_path, digest)?,
+        None if digest_path.exists() => fs::remove_file(digest_path)?,
+        None => (),
+    }
+
     github::clear_downloads(OWNER, REPOSITORY)?;
 
     Ok(())
@@ -0,0 +1,311 @@
+use anyhow::{Context, Result};
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{project, utils::Validators};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Entries younger than this are never collected, another project may be about to reference them
+const GC_GRACE_PERIOD: Duration = Duration::from_hours(1);
+
+/// Dependencies a project uses, stored in its dependency dir
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Root of the project, used to forget projects that don't exist anymore
+    pub root: PathBuf,
+
+    /// Store entry (SHA-256 of the downloaded archive) per dependency url
+    pub dependencies: BTreeMap<String, String>,
+
+    /// HTTP validators per dependency url, so only archives that changed are downloaded again
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub validators: BTreeMap<String, Validators>,
+}
+
+impl Manifest {
+    /// An empty manifest if the project has none yet
+    pub fn load(project_deps_dir: &Path) -> Result<Self> {
+        let path = project_deps_dir.join(MANIFEST_FILE);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("could not parse {}", path.display()))
+    }
+
+    pub fn save(&self, project_deps_dir: &Path) -> Result<()> {
+        let path = project_deps_dir.join(MANIFEST_FILE);
+        let tmp = path.with_extension("tmp");
+
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, &path)?;
+
+        Ok(())
+    }
+}
+
+fn store_dir(deps_root: &Path) -> PathBuf {
+    deps_root.join("store")
+}
+
+fn projects_dir(deps_root: &Path) -> PathBuf {
+    deps_root.join("project")
+}
+
+/// Extracted library for the archive with the SHA-256 `hash`
+pub fn entry_path(hash: &str) -> Result<PathBuf> {
+    Ok(store_dir(&project::deps_root()?).join(hash))
+}
+
+/// Where an entry is put together before it's moved into place with [`commit`]
+pub fn staging_path(hash: &str) -> Result<PathBuf> {
+    let path = store_dir(&project::deps_root()?).join(format!("{hash}.{}", std::process::id()));
+
+    if path.exists() {
+        fs::remove_dir_all(&path)?;
+    }
+
+    Ok(path)
+}
+
+/// Moves a staged entry into the store, unless another process was faster
+pub fn commit(staging: &Path, hash: &str) -> Result<PathBuf> {
+    let entry = entry_path(hash)?;
+
+    if entry.exists() {
+        fs::remove_dir_all(staging)?;
+        return Ok(entry);
+    }
+
+    if !staging.exists() {
+        // archive without any lua files, still worth remembering it's been processed
+        fs::create_dir_all(staging)?;
+    }
+
+    fs::rename(staging, &entry)?;
+
+    Ok(entry)
+}
+
+fn manifests(deps_root: &Path) -> Result<Vec<(PathBuf, Manifest)>> {
+    let dir = projects_dir(deps_root);
+
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut manifests = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if !path.join(MANIFEST_FILE).exists() {
+            continue;
+        }
+
+        match Manifest::load(&path) {
+            Ok(manifest) => manifests.push((path, manifest)),
+            Err(err) => tracing::error!("Skipping dependency manifest: {err:?}"),
+        }
+    }
+
+    Ok(manifests)
+}
+
+/// Store entry another project already installed `url` as, with the validators it was downloaded with
+pub fn find(url: &str) -> Result<Option<(String, Validators)>> {
+    find_in(&project::deps_root()?, url)
+}
+
+fn find_in(deps_root: &Path, url: &str) -> Result<Option<(String, Validators)>> {
+    let Some((hash, validators)) = manifests(deps_root)?
+        .into_iter()
+        .find_map(|(_, mut manifest)| {
+            let hash = manifest.dependencies.remove(url)?;
+            Some((hash, manifest.validators.remove(url).unwrap_or_default()))
+        })
+        .filter(|(hash, _)| store_dir(deps_root).join(hash).exists())
+    else {
+        return Ok(None);
+    };
+
+    // the other project may stop using it before our manifest references it, the grace period
+    // only protects it from garbage collection in between if it counts from now
+    filetime::set_file_mtime(store_dir(deps_root).join(&hash), FileTime::now())?;
+
+    Ok(Some((hash, validators)))
+}
+
+/// Removes store entries no project references anymore and returns how many. With `prune_projects`
+/// the manifests of projects that don't exist anymore are removed first
+pub fn collect_garbage(prune_projects: bool) -> Result<usize> {
+    collect_garbage_in(&project::deps_root()?, prune_projects, GC_GRACE_PERIOD)
+}
+
+fn collect_garbage_in(deps_root: &Path, prune_projects: bool, grace: Duration) -> Result<usize> {
+    let mut referenced = HashSet::new();
+
+    for (dir, manifest) in manifests(deps_root)? {
+        if prune_projects && !manifest.root.join("game.project").exists() {
+            tracing::info!(
+                "Forgetting dependencies of {}, the project doesn't exist anymore",
+                manifest.root.display()
+            );
+            fs::remove_dir_all(dir)?;
+            continue;
+        }
+
+        referenced.extend(manifest.dependencies.into_values());
+    }
+
+    let store = store_dir(deps_root);
+
+    if !store.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+
+    for entry in fs::read_dir(store)? {
+        let path = entry?.path();
+
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        if referenced.contains(name) {
+            continue;
+        }
+
+        if path.metadata()?.modified()?.elapsed().unwrap_or_default() < grace {
+            continue;
+        }
+
+        tracing::debug!("Removing unused dependency {}", path.display());
+        fs::remove_dir_all(&path)?;
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dependency_store::{Manifest, collect_garbage_in, find_in, projects_dir, store_dir};
+    use filetime::FileTime;
+    use pretty_assertions::assert_eq;
+    use std::{
+        collections::BTreeMap,
+        env, fs,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn test_collect_garbage() {
+        let deps_root = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("dependency-store-{}", std::process::id()));
+
+        let game_root = deps_root.join("game");
+        fs::create_dir_all(&game_root).unwrap();
+        fs::write(game_root.join("game.project"), "").unwrap();
+
+        for hash in ["aaa", "bbb", "ccc", "ddd.1234"] {
+            fs::create_dir_all(store_dir(&deps_root).join(hash)).unwrap();
+        }
+
+        let existing = projects_dir(&deps_root).join("existing");
+        fs::create_dir_all(&existing).unwrap();
+        Manifest {
+            root: game_root.clone(),
+            dependencies: BTreeMap::from([("https://a.zip".to_string(), "aaa".to_string())]),
+            ..Default::default()
+        }
+        .save(&existing)
+        .unwrap();
+
+        let gone = projects_dir(&deps_root).join("gone");
+        fs::create_dir_all(&gone).unwrap();
+        Manifest {
+            root: deps_root.join("deleted game"),
+            dependencies: BTreeMap::from([("https://b.zip".to_string(), "bbb".to_string())]),
+            ..Default::default()
+        }
+        .save(&gone)
+        .unwrap();
+
+        // everything is too young to collect
+        assert_eq!(
+            0,
+            collect_garbage_in(&deps_root, false, Duration::from_hours(1)).unwrap()
+        );
+
+        // ccc is unused, so is the leftover staging dir
+        assert_eq!(
+            2,
+            collect_garbage_in(&deps_root, false, Duration::ZERO).unwrap()
+        );
+        assert!(store_dir(&deps_root).join("bbb").exists());
+
+        // bbb is only used by a project that's gone
+        assert_eq!(
+            1,
+            collect_garbage_in(&deps_root, true, Duration::ZERO).unwrap()
+        );
+        assert!(!gone.exists());
+        assert!(store_dir(&deps_root).join("aaa").exists());
+
+        fs::remove_dir_all(&deps_root).unwrap();
+    }
+
+    #[test]
+    fn test_find_protects_entry_from_garbage_collection() {
+        let deps_root = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("dependency-store-find-{}", std::process::id()));
+
+        let entry = store_dir(&deps_root).join("aaa");
+        fs::create_dir_all(&entry).unwrap();
+        filetime::set_file_mtime(
+            &entry,
+            FileTime::from_system_time(SystemTime::now() - Duration::from_hours(2)),
+        )
+        .unwrap();
+
+        let other = projects_dir(&deps_root).join("other");
+        fs::create_dir_all(&other).unwrap();
+        Manifest {
+            root: deps_root.join("other game"),
+            dependencies: BTreeMap::from([("https://a.zip".to_string(), "aaa".to_string())]),
+            ..Default::default()
+        }
+        .save(&other)
+        .unwrap();
+
+        assert_eq!(None, find_in(&deps_root, "https://b.zip").unwrap());
+        assert_eq!(
+            "aaa",
+            find_in(&deps_root, "https://a.zip").unwrap().unwrap().0
+        );
+
+        // the other project drops it before ours references it
+        fs::remove_dir_all(&other).unwrap();
+
+        assert_eq!(
+            0,
+            collect_garbage_in(&deps_root, false, Duration::from_hours(1)).unwrap()
+        );
+        assert!(entry.exists());
+
+        fs::remove_dir_all(&deps_root).unwrap();
+    }
+}
@@ -5,18 +5,27 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{bridge, editor, path};
+use crate::{bridge, editor, focus::FocusSettings, path, project_id::ProjectId};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum LauncherType {
     #[serde(rename = "neovide")]
     Neovide,
 
     #[serde(rename = "terminal")]
     Terminal,
+
+    #[serde(rename = "tmux")]
+    Tmux,
+
+    #[serde(rename = "zellij")]
+    Zellij,
+
+    #[serde(rename = "wezterm")]
+    WezTerm,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum SocketType {
     #[serde(rename = "fsock")]
     Fsock,
@@ -25,7 +34,7 @@ pub enum SocketType {
     Netsock,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct LauncherSettings {
     #[serde(rename = "type")]
     pub launcher_type: Option<LauncherType>,
@@ -37,15 +46,24 @@ pub struct LauncherSettings {
 }
 
 impl LauncherSettings {
+    /// Only the settings that are set, everything else is left to the config files
     #[must_use]
     pub fn bridge_pre_cli_args(&self) -> Vec<String> {
         let mut args = Vec::new();
 
-        args.push("--launcher-type".to_string());
-        args.push(match self.launcher_type {
-            Some(LauncherType::Neovide) | None => "neovide".to_string(),
-            Some(LauncherType::Terminal) => "terminal".to_string(),
-        });
+        if let Some(launcher_type) = &self.launcher_type {
+            args.push("--launcher-type".to_string());
+            args.push(
+                match launcher_type {
+                    LauncherType::Neovide => "neovide",
+                    LauncherType::Terminal => "terminal",
+                    LauncherType::Tmux => "tmux",
+                    LauncherType::Zellij => "zellij",
+                    LauncherType::WezTerm => "wezterm",
+                }
+                .to_string(),
+            );
+        }
 
         if let Some(socket_type) = &self.socket_type {
             args.push("--socket-type".to_string());
@@ -98,39 +116,194 @@ const SCRIPT_EXT: &'static str = "bat";
 #[cfg(not(target_os = "windows"))]
 const SCRIPT_EXT: &str = "sh";
 
+#[cfg(target_os = "windows")]
+const SCRIPT_TARGET: ScriptTarget = ScriptTarget::Batch;
+
+#[cfg(not(target_os = "windows"))]
+const SCRIPT_TARGET: ScriptTarget = ScriptTarget::Sh;
+
+#[derive(Debug, Clone, Copy)]
+enum ScriptTarget {
+    #[cfg_attr(target_os = "windows", allow(dead_code))]
+    Sh,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    Batch,
+}
+
+impl ScriptTarget {
+    fn quote(self, arg: &str) -> Result<String> {
+        match self {
+            ScriptTarget::Sh => Ok(format!("'{}'", arg.replace('\'', r"'\''"))),
+            ScriptTarget::Batch => {
+                if arg.contains(['\n', '\r']) {
+                    bail!("Argument {arg:?} can not be passed through a batch script");
+                }
+
+                // quotes are escaped as "" instead of \" so that cmd.exe never leaves the
+                // quoted section, backslashes only need doubling in front of a quote
+                let mut quoted = String::from("\"");
+                let mut backslashes = 0;
+
+                for c in arg.chars() {
+                    match c {
+                        '\\' => backslashes += 1,
+                        '"' => {
+                            quoted.push_str(&"\\".repeat(backslashes * 2));
+                            quoted.push_str("\"\"");
+                            backslashes = 0;
+                        }
+                        c => {
+                            quoted.push_str(&"\\".repeat(backslashes));
+                            backslashes = 0;
+
+                            if c == '%' {
+                                quoted.push_str("%%");
+                            } else {
+                                quoted.push(c);
+                            }
+                        }
+                    }
+                }
+
+                quoted.push_str(&"\\".repeat(backslashes * 2));
+                quoted.push('"');
+
+                Ok(quoted)
+            }
+        }
+    }
+
+    fn quote_all(self, args: &[String]) -> Result<String> {
+        Ok(args
+            .iter()
+            .map(|arg| self.quote(arg))
+            .collect::<Result<Vec<_>>>()?
+            .join(" "))
+    }
+}
+
+fn render_script(
+    template: &str,
+    target: ScriptTarget,
+    bridge_path: &Path,
+    launcher_settings: &LauncherSettings,
+    focus_settings: &FocusSettings,
+) -> Result<String> {
+    let bridge_path = bridge_path
+        .to_str()
+        .context("could not convert bridge path")?;
+
+    let mut pre_args = launcher_settings.bridge_pre_cli_args();
+    pre_args.extend(focus_settings.bridge_cli_args());
+
+    Ok(template
+        .replace("{BRIDGE_PATH}", &target.quote(bridge_path)?)
+        .replace("{LAUNCH_PRE_ARGS}", &target.quote_all(&pre_args)?)
+        .replace(
+            "{DEBUG_FLAG}",
+            if let Some(debug) = launcher_settings.debug
+                && debug
+            {
+                "--debug"
+            } else {
+                ""
+            },
+        )
+        .replace(
+            "{LAUNCH_POST_ARGS}",
+            &target.quote_all(&launcher_settings.bridge_post_cli_args())?,
+        ))
+}
+
+/// Renders the runner script for the current platform calling the bridge at `bridge_path`
+pub fn render_runner_script(
+    bridge_path: &Path,
+    launcher_settings: &LauncherSettings,
+    focus_settings: &FocusSettings,
+) -> Result<String> {
+    render_script(
+        RUN_SCRIPT,
+        SCRIPT_TARGET,
+        bridge_path,
+        launcher_settings,
+        focus_settings,
+    )
+}
+
+fn runners_dir() -> Result<PathBuf> {
+    Ok(path::data_dir()?.join("runners"))
+}
+
+/// Removes runner scripts of projects that don't exist anymore. The global runner script older
+/// versions created is kept, the editor prefs of other projects may still point at it
+fn cleanup_stale_runner_scripts(dir: &Path) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let runner_dir = entry?.path();
+
+        if !runner_dir.is_dir() {
+            continue;
+        }
+
+        let is_stale = fs::read_to_string(runner_dir.join("root"))
+            .map(|root| !Path::new(root.trim()).join("game.project").exists())
+            .unwrap_or(true);
+
+        if is_stale {
+            tracing::debug!("Removing stale runner script dir {}", runner_dir.display());
+            fs::remove_dir_all(runner_dir)?;
+        }
+    }
+
+    Ok(())
+}
+
 fn create_runner_script(
+    root_dir: &Path,
     plugin_root: &Path,
     launcher_settings: &LauncherSettings,
+    focus_settings: &FocusSettings,
 ) -> Result<PathBuf> {
-    let dir = path::data_dir()?.join("bin");
+    let runners_dir = runners_dir()?;
+
+    if let Err(err) = cleanup_stale_runner_scripts(&runners_dir) {
+        tracing::error!("Could not clean up stale runner scripts: {err:?}");
+    }
+
+    write_runner_script(
+        &runners_dir,
+        root_dir,
+        &bridge::path(Some(plugin_root))?,
+        launcher_settings,
+        focus_settings,
+    )
+}
+
+fn write_runner_script(
+    runners_dir: &Path,
+    root_dir: &Path,
+    bridge_path: &Path,
+    launcher_settings: &LauncherSettings,
+    focus_settings: &FocusSettings,
+) -> Result<PathBuf> {
+    let dir = runners_dir.join(ProjectId::from_root(root_dir)?.as_str());
     fs::create_dir_all(&dir)?;
 
+    fs::write(
+        dir.join("root"),
+        root_dir
+            .to_str()
+            .context("could not convert root dir to string")?,
+    )?;
+
     let script_path = dir.join(format!("run.{SCRIPT_EXT}"));
-    let bridge_path = bridge::path(Some(plugin_root))?;
-    let launch_pre_args = launcher_settings.bridge_pre_cli_args().join(" ");
-    let launch_post_args = launcher_settings.bridge_post_cli_args().join(" ");
 
     fs::write(
         &script_path,
-        RUN_SCRIPT
-            .replace(
-                "{BRIDGE_PATH}",
-                bridge_path
-                    .to_str()
-                    .context("could not convert bridge path")?,
-            )
-            .replace("{LAUNCH_PRE_ARGS}", &launch_pre_args)
-            .replace(
-                "{DEBUG_FLAG}",
-                if let Some(debug) = launcher_settings.debug
-                    && debug
-                {
-                    "--debug"
-                } else {
-                    ""
-                },
-            )
-            .replace("{LAUNCH_POST_ARGS}", &launch_post_args),
+        render_runner_script(bridge_path, launcher_settings, focus_settings)?,
     )?;
 
     #[cfg(not(target_os = "windows"))]
@@ -142,22 +315,89 @@ fn create_runner_script(
     Ok(script_path)
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct EditorConfig {
-    #[serde(rename = "custom-editor")]
+    #[serde(rename = "custom-editor", default)]
     custom_editor: String,
 
-    #[serde(rename = "open-file")]
+    #[serde(rename = "open-file", default)]
     open_file: String,
 
-    #[serde(rename = "open-file-at-line")]
+    #[serde(rename = "open-file-at-line", default)]
     open_file_at_line: String,
 }
 
+fn prefs_url(port: u16) -> String {
+    format!("http://localhost:{port}/prefs/code")
+}
+
+fn backup_path(root_dir: &Path) -> Result<PathBuf> {
+    let dir = path::data_dir()?.join("editor_prefs");
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir.join(format!("{}.json", ProjectId::from_root(root_dir)?)))
+}
+
+fn fetch_editor_config(port: u16) -> Result<EditorConfig> {
+    let res = reqwest::blocking::get(prefs_url(port))?;
+
+    if !res.status().is_success() {
+        bail!("could not read editor prefs, status: {:?}", res.status());
+    }
+
+    Ok(res.json()?)
+}
+
+fn apply_editor_config(port: u16, config: &EditorConfig) -> Result<()> {
+    let res = reqwest::blocking::Client::new()
+        .post(prefs_url(port))
+        .json(config)
+        .send()?;
+
+    if !res.status().is_success() {
+        bail!("could not write editor prefs, status: {:?}", res.status());
+    }
+
+    Ok(())
+}
+
+fn is_own_runner_script(custom_editor: &str) -> bool {
+    path::data_dir()
+        .map(|dir| {
+            let path = Path::new(custom_editor);
+            path.starts_with(dir.join("runners")) || path.starts_with(dir.join("bin"))
+        })
+        .unwrap_or_default()
+}
+
+/// Remembers the editors current code preferences so they can be restored later,
+/// unless they're already pointing at one of our runner scripts
+fn backup_editor_config(port: u16, backup: &Path) -> Result<()> {
+    let current = fetch_editor_config(port)?;
+
+    if is_own_runner_script(&current.custom_editor) {
+        tracing::debug!("Editor is already using defold.nvim, keeping existing backup");
+        return Ok(());
+    }
+
+    tracing::debug!(
+        "Backing up editor prefs {current:?} to {}",
+        backup.display()
+    );
+
+    fs::write(backup, serde_json::to_string(&current)?)?;
+
+    Ok(())
+}
+
+/// Points the editor at the runner script of the project. The launcher settings are passed to the
+/// bridge as flags, whatever isn't set there is read from the config files
 pub fn set_default_editor(
     port: u16,
+    root_dir: &Path,
     plugin_root: &Path,
     launcher_settings: &LauncherSettings,
+    focus_settings: &FocusSettings,
 ) -> Result<()> {
     if !editor::is_editor_port(port) {
         bail!("No edito was found runnign at {port}");
@@ -167,21 +407,225 @@ pub fn set_default_editor(
         bail!("plugin root '{}' could not be found", plugin_root.display());
     }
 
+    ProjectId::migrate(root_dir)?;
+
+    // without a backup the prefs could never be restored, so they're left alone
+    backup_editor_config(port, &backup_path(root_dir)?)
+        .context("could not back up editor prefs, not changing them")?;
+
     let config = EditorConfig {
-        custom_editor: create_runner_script(plugin_root, launcher_settings)?
-            .to_str()
-            .context("could not convert path to string")?
-            .to_string(),
+        custom_editor: create_runner_script(
+            root_dir,
+            plugin_root,
+            launcher_settings,
+            focus_settings,
+        )?
+        .to_str()
+        .context("could not convert path to string")?
+        .to_string(),
         open_file: "{file}".to_string(),
         open_file_at_line: "{file} {line}".to_string(),
     };
 
-    let url = format!("http://localhost:{port}/prefs/code");
+    apply_editor_config(port, &config)
+}
 
-    reqwest::blocking::Client::new()
-        .post(url)
-        .json(&config)
-        .send()?;
+/// Restores the code preferences the editor had before `set_default_editor` was called
+pub fn unset_default_editor(port: u16, root_dir: &Path) -> Result<()> {
+    if !editor::is_editor_port(port) {
+        bail!("No editor was found running at {port}");
+    }
+
+    restore_editor_config(port, &backup_path(root_dir)?)
+}
+
+/// Without a backup the prefs are only reset if they still point at one of our runner scripts,
+/// anything else was set by the user
+fn restore_editor_config(port: u16, backup: &Path) -> Result<()> {
+    let config = if backup.exists() {
+        serde_json::from_str(&fs::read_to_string(backup)?)?
+    } else if is_own_runner_script(&fetch_editor_config(port)?.custom_editor) {
+        tracing::debug!("No editor prefs backup found, resetting to the built-in editor");
+        EditorConfig::default()
+    } else {
+        bail!(
+            "No editor prefs backup found and the editor isn't using defold.nvim, leaving them alone"
+        );
+    };
+
+    apply_editor_config(port, &config)?;
+
+    if backup.exists() {
+        fs::remove_file(backup)?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        editor_config::{
+            EditorConfig, LauncherSettings, ScriptTarget, apply_editor_config,
+            backup_editor_config, cleanup_stale_runner_scripts, fetch_editor_config,
+            restore_editor_config, write_runner_script,
+        },
+        focus::FocusSettings,
+        path,
+        test_utils::{Response, serve},
+    };
+    use pretty_assertions::assert_eq;
+    use std::{env, fs, sync::Mutex};
+
+    /// Stand-in for the editor's `/prefs/code` endpoint
+    fn serve_prefs(prefs: &str) -> u16 {
+        let prefs = Mutex::new(prefs.to_string());
+
+        let (url, _) = serve(move |request| {
+            let mut prefs = prefs.lock().unwrap();
+
+            if request.method == "post" {
+                prefs.clone_from(&request.body);
+            }
+
+            Response::ok(prefs.clone()).header("Content-Type", "application/json")
+        });
+
+        url.rsplit(':').next().unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_runner_scripts_per_project() {
+        let dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("runners-{}", std::process::id()));
+        let runners_dir = dir.join("runners");
+        let bridge_path = dir.join("bridge");
+
+        let games = ["game", "other game"].map(|name| {
+            let root = dir.join(name);
+            fs::create_dir_all(&root).unwrap();
+            fs::write(root.join("game.project"), "").unwrap();
+            root
+        });
+
+        let scripts = games.clone().map(|root| {
+            write_runner_script(
+                &runners_dir,
+                &root,
+                &bridge_path,
+                &LauncherSettings::default(),
+                &FocusSettings::default(),
+            )
+            .unwrap()
+        });
+
+        assert!(scripts[0] != scripts[1]);
+        assert!(
+            scripts
+                .iter()
+                .all(|script| script.starts_with(&runners_dir))
+        );
+        assert!(
+            fs::read_to_string(&scripts[0])
+                .unwrap()
+                .contains(bridge_path.to_str().unwrap())
+        );
+
+        // the first game is gone, only its runner script is removed
+        fs::remove_dir_all(&games[0]).unwrap();
+        cleanup_stale_runner_scripts(&runners_dir).unwrap();
+
+        assert!(!scripts[0].parent().unwrap().exists());
+        assert!(scripts[1].exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_and_restore_editor_config() {
+        let port = serve_prefs(
+            r#"{"custom-editor":"/usr/bin/code","open-file":"{file}","open-file-at-line":"{file}:{line}"}"#,
+        );
+
+        let backup = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("editor-prefs-{}.json", std::process::id()));
+        fs::create_dir_all(backup.parent().unwrap()).unwrap();
+
+        backup_editor_config(port, &backup).unwrap();
+
+        apply_editor_config(
+            port,
+            &EditorConfig {
+                custom_editor: "/tmp/run.sh".to_string(),
+                open_file: "{file}".to_string(),
+                open_file_at_line: "{file} {line}".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            "/tmp/run.sh",
+            fetch_editor_config(port).unwrap().custom_editor
+        );
+
+        restore_editor_config(port, &backup).unwrap();
+
+        let restored = fetch_editor_config(port).unwrap();
+        assert_eq!("/usr/bin/code", restored.custom_editor);
+        assert_eq!("{file}:{line}", restored.open_file_at_line);
+        assert!(!backup.exists());
+
+        // without a backup prefs the user set themselves are left alone
+        assert!(restore_editor_config(port, &backup).is_err());
+        assert_eq!(
+            "/usr/bin/code",
+            fetch_editor_config(port).unwrap().custom_editor
+        );
+
+        // but our own runner script is still removed
+        let runner = path::data_dir()
+            .unwrap()
+            .join("runners")
+            .join("1234abcd")
+            .join("run.sh");
+        apply_editor_config(
+            port,
+            &EditorConfig {
+                custom_editor: runner.to_str().unwrap().to_string(),
+                ..EditorConfig::default()
+            },
+        )
+        .unwrap();
+        restore_editor_config(port, &backup).unwrap();
+        assert_eq!("", fetch_editor_config(port).unwrap().custom_editor);
+    }
+
+    #[test]
+    fn test_quote_sh() {
+        let quote = |s| ScriptTarget::Sh.quote(s).unwrap();
+
+        assert_eq!("'kitty'", quote("kitty"));
+        assert_eq!("''", quote(""));
+        assert_eq!("'/opt/my apps/kitty'", quote("/opt/my apps/kitty"));
+        assert_eq!(r"'it'\''s'", quote("it's"));
+        assert_eq!(r#"'$HOME "x" `id`'"#, quote(r#"$HOME "x" `id`"#));
+    }
+
+    #[test]
+    fn test_quote_batch() {
+        let quote = |s| ScriptTarget::Batch.quote(s).unwrap();
+
+        assert_eq!(r#""kitty""#, quote("kitty"));
+        assert_eq!(r#""""#, quote(""));
+        assert_eq!(
+            r#""C:\Program Files\kitty.exe""#,
+            quote(r"C:\Program Files\kitty.exe")
+        );
+        assert_eq!(r#""say ""hi"" & exit""#, quote(r#"say "hi" & exit"#));
+        assert_eq!(r#""100%% sure""#, quote("100% sure"));
+        assert_eq!(r#""C:\dir\\""#, quote(r"C:\dir\"));
+        assert_eq!(r#""a\\""b""#, quote(r#"a\"b"#));
+        assert!(ScriptTarget::Batch.quote("a\nb").is_err());
+    }
+}
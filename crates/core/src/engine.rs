@@ -0,0 +1,147 @@
+use std::path::Path;
+
+/// Finds the most recently started engine running the project. Only implemented on Linux,
+/// where `/proc` can be searched, everywhere else this is always `None`
+#[must_use]
+pub fn find_pid(root_dir: &Path) -> Option<u32> {
+    #[cfg(target_os = "linux")]
+    return find_pid_in(Path::new("/proc"), &dunce::canonicalize(root_dir).ok()?);
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = root_dir;
+        None
+    }
+}
+
+/// The editor starts the engine either from the project's build dir or with the project
+/// passed on the command line, so any of them pointing into the project means it's ours
+#[cfg(target_os = "linux")]
+fn find_pid_in(proc_dir: &Path, root_dir: &Path) -> Option<u32> {
+    use std::fs;
+
+    let mut newest: Option<(u64, u32)> = None;
+
+    for entry in fs::read_dir(proc_dir).ok()?.filter_map(Result::ok) {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+
+        let dir = entry.path();
+
+        if !fs::read_to_string(dir.join("comm")).is_ok_and(|comm| comm.starts_with("dmengine")) {
+            continue;
+        }
+
+        let in_project =
+            |link: &str| fs::read_link(dir.join(link)).is_ok_and(|path| path.starts_with(root_dir));
+
+        let in_cmdline = fs::read(dir.join("cmdline")).is_ok_and(|cmdline| {
+            cmdline
+                .split(|b| *b == 0)
+                .any(|arg| Path::new(&*String::from_utf8_lossy(arg)).starts_with(root_dir))
+        });
+
+        if !in_project("cwd") && !in_project("exe") && !in_cmdline {
+            continue;
+        }
+
+        // start time is the 22nd field, counting from after the command name
+        let started = fs::read_to_string(dir.join("stat"))
+            .ok()
+            .and_then(|stat| {
+                stat.rsplit_once(')')
+                    .and_then(|(_, fields)| fields.split_whitespace().nth(19)?.parse().ok())
+            })
+            .unwrap_or_default();
+
+        tracing::debug!("Found engine process {pid} for {}", root_dir.display());
+
+        if newest.is_none_or(|(newest_started, _)| started > newest_started) {
+            newest = Some((started, pid));
+        }
+    }
+
+    newest.map(|(_, pid)| pid)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use crate::engine::find_pid_in;
+    use pretty_assertions::assert_eq;
+    use std::{env, fs, os::unix::fs::symlink, path::Path};
+
+    fn fake_process(
+        proc_dir: &Path,
+        pid: u32,
+        comm: &str,
+        cwd: &Path,
+        cmdline: &[&str],
+        started: u64,
+    ) {
+        let dir = proc_dir.join(pid.to_string());
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(dir.join("comm"), format!("{comm}\n")).unwrap();
+        symlink(cwd, dir.join("cwd")).unwrap();
+        fs::write(dir.join("cmdline"), cmdline.join("\0")).unwrap();
+        fs::write(
+            dir.join("stat"),
+            format!("{pid} ({comm}) S 1 1 1 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 {started} 0 0"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_find_engine_pid() {
+        let dir = env::temp_dir().join(format!("defold-nvim-engine-{}", std::process::id()));
+        let proc_dir = dir.join("proc");
+        let root = dir.join("my game");
+        let other = dir.join("other");
+        fs::create_dir_all(&proc_dir).unwrap();
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&other).unwrap();
+
+        assert_eq!(None, find_pid_in(&proc_dir, &root));
+
+        let projectc = root.join("build/default/game.projectc");
+
+        fake_process(&proc_dir, 10, "dmengine", &root, &["./dmengine"], 500);
+        fake_process(&proc_dir, 11, "nvim", &root, &["nvim"], 900);
+        fake_process(&proc_dir, 12, "dmengine", &other, &["./dmengine"], 1000);
+        fake_process(
+            &proc_dir,
+            13,
+            "dmengine",
+            &other,
+            &["./dmengine", projectc.to_str().unwrap()],
+            700,
+        );
+
+        assert_eq!(Some(13), find_pid_in(&proc_dir, &root));
+        assert_eq!(Some(12), find_pid_in(&proc_dir, &other));
+
+        // a project next to ours whose path merely starts with the same characters
+        let sibling = dir.join("my game 2");
+        fs::create_dir_all(&sibling).unwrap();
+        fake_process(
+            &proc_dir,
+            14,
+            "dmengine",
+            &other,
+            &[
+                "./dmengine",
+                sibling
+                    .join("build/default/game.projectc")
+                    .to_str()
+                    .unwrap(),
+            ],
+            1200,
+        );
+
+        assert_eq!(Some(13), find_pid_in(&proc_dir, &root));
+        assert_eq!(Some(14), find_pid_in(&proc_dir, &sibling));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,18 +1,87 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::process::Command;
 use which::which;
 
 use strum::IntoEnumIterator;
 
-use crate::{game_project::GameProject, utils::classname};
+use crate::{config, engine, game_project::GameProject, multiplexer, project_id::ProjectId};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FocusSettings {
+    /// Switcher to use, picked based on the running desktop if not set
+    pub switcher: Option<Switcher>,
+
+    /// Custom command used instead of a switcher, `{class}`, `{title}` and `{pid}` get
+    /// replaced with the window class, title or process id
+    pub command: Option<Vec<String>>,
+}
+
+impl FocusSettings {
+    /// Only the settings that are set, everything else is left to the global config
+    #[must_use]
+    pub fn bridge_cli_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(switcher) = self.switcher {
+            args.push("--focus-switcher".to_string());
+            args.push(switcher.executable_name().to_string());
+        }
+
+        for arg in self.command.iter().flatten() {
+            args.push("--focus-command".to_string());
+            args.push(arg.clone());
+        }
+
+        args
+    }
+
+    /// Fills every setting that isn't set from `settings`
+    #[must_use]
+    pub fn or_settings(self, settings: FocusSettings) -> Self {
+        Self {
+            switcher: self.switcher.or(settings.switcher),
+            command: self.command.or(settings.command),
+        }
+    }
+}
+
+/// Outcome of switching focus to a window
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct FocusResult {
+    /// Switcher that ran, `custom` for a custom command and nothing if none could be found
+    pub backend: Option<String>,
+
+    /// Whether a matching window was found and focused
+    pub window_found: bool,
+
+    /// What the switcher printed to stderr
+    pub stderr: String,
+}
+
+impl FocusResult {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fn not_found(backend: &str) -> Self {
+        Self {
+            backend: Some(backend.to_string()),
+            window_found: false,
+            stderr: String::new(),
+        }
+    }
+}
 
 #[derive(Debug)]
 enum SwitcherType {
     Class(String),
     Title(String),
     AppName(String),
+    Pid(u32),
 }
 
 impl SwitcherType {
@@ -21,172 +90,841 @@ impl SwitcherType {
             SwitcherType::Class(c) => c.clone(),
             SwitcherType::Title(t) => t.clone(),
             SwitcherType::AppName(a) => a.clone(),
+            SwitcherType::Pid(p) => p.to_string(),
+        }
+    }
+
+    /// Whether a window's JSON description (niri, Window Calls) has a matching `key`
+    #[cfg(target_os = "linux")]
+    fn matches(&self, window: &Value, key: &str) -> bool {
+        match self {
+            SwitcherType::Pid(pid) => window[key].as_u64() == Some(u64::from(*pid)),
+            _ => window[key].as_str() == Some(&self.value()),
         }
     }
 }
 
-#[derive(Debug, strum::EnumIter)]
-enum Switcher {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::EnumIter)]
+pub enum Switcher {
     #[cfg(target_os = "linux")]
+    #[serde(rename = "hyprctl")]
     HyprCtl,
 
     #[cfg(target_os = "linux")]
+    #[serde(rename = "swaymsg")]
     SwayMsg,
 
     #[cfg(target_os = "linux")]
+    #[serde(rename = "wmctrl")]
     WmCtrl,
 
     #[cfg(target_os = "linux")]
+    #[serde(rename = "xdotool")]
     XDoTool,
 
+    #[cfg(target_os = "linux")]
+    #[serde(rename = "niri")]
+    Niri,
+
+    #[cfg(target_os = "linux")]
+    #[serde(rename = "kdotool")]
+    KDoTool,
+
+    #[cfg(target_os = "linux")]
+    #[serde(rename = "i3-msg")]
+    I3Msg,
+
+    /// Requires the "Window Calls" GNOME Shell extension
+    #[cfg(target_os = "linux")]
+    #[serde(rename = "gdbus")]
+    Gnome,
+
     #[cfg(target_os = "macos")]
+    #[serde(rename = "osascript")]
     OsaScript,
 }
 
+impl FromStr for Switcher {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Switcher::iter().find(|switcher| switcher.executable_name() == s) {
+            Some(switcher) => Ok(switcher),
+            None => bail!("Unknown focus switcher {s}"),
+        }
+    }
+}
+
 impl Switcher {
-    fn path(&self) -> Option<PathBuf> {
-        #[cfg(target_os = "linux")]
+    fn executable_name(self) -> &'static str {
         match self {
-            Switcher::HyprCtl => which("hyprctl").ok(),
-            Switcher::SwayMsg => which("swaymsg").ok(),
-            Switcher::WmCtrl => which("wmctrl").ok(),
-            Switcher::XDoTool => which("xdotool").ok(),
+            #[cfg(target_os = "linux")]
+            Switcher::HyprCtl => "hyprctl",
+            #[cfg(target_os = "linux")]
+            Switcher::SwayMsg => "swaymsg",
+            #[cfg(target_os = "linux")]
+            Switcher::WmCtrl => "wmctrl",
+            #[cfg(target_os = "linux")]
+            Switcher::XDoTool => "xdotool",
+            #[cfg(target_os = "linux")]
+            Switcher::Niri => "niri",
+            #[cfg(target_os = "linux")]
+            Switcher::KDoTool => "kdotool",
+            #[cfg(target_os = "linux")]
+            Switcher::I3Msg => "i3-msg",
+            #[cfg(target_os = "linux")]
+            Switcher::Gnome => "gdbus",
+            #[cfg(target_os = "macos")]
+            Switcher::OsaScript => "osascript",
         }
-        #[cfg(target_os = "macos")]
+    }
+
+    fn path(self) -> Option<PathBuf> {
+        which(self.executable_name()).ok()
+    }
+
+    /// Only works while the matching compositor is running
+    fn is_compositor_specific(self) -> bool {
         match self {
-            Switcher::OsaScript => which("osascript").ok(),
+            #[cfg(target_os = "linux")]
+            Switcher::HyprCtl
+            | Switcher::SwayMsg
+            | Switcher::Niri
+            | Switcher::KDoTool
+            | Switcher::I3Msg
+            | Switcher::Gnome => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
         }
-        #[cfg(target_os = "windows")]
-        None
     }
 
     fn from_env() -> Option<Self> {
-        Self::iter().find(|sw| sw.path().is_some())
+        Self::detect(|name| std::env::var(name).ok(), |sw| sw.path().is_some())
+    }
+
+    /// Prefers the switcher of the compositor that is actually running, having e.g. wmctrl
+    /// installed doesn't mean it works under Hyprland
+    fn detect(
+        var: impl Fn(&str) -> Option<String>,
+        available: impl Fn(Self) -> bool,
+    ) -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            let desktops = var("XDG_CURRENT_DESKTOP")
+                .unwrap_or_default()
+                .to_lowercase();
+            let is_desktop = |name: &str| desktops.split(':').any(|desktop| desktop == name);
+
+            let compositor =
+                if var("HYPRLAND_INSTANCE_SIGNATURE").is_some() || is_desktop("hyprland") {
+                    Some(Switcher::HyprCtl)
+                } else if var("SWAYSOCK").is_some() || is_desktop("sway") {
+                    Some(Switcher::SwayMsg)
+                } else if var("NIRI_SOCKET").is_some() || is_desktop("niri") {
+                    Some(Switcher::Niri)
+                } else if var("I3SOCK").is_some() || is_desktop("i3") {
+                    Some(Switcher::I3Msg)
+                } else if is_desktop("kde") {
+                    Some(Switcher::KDoTool)
+                } else if is_desktop("gnome") {
+                    Some(Switcher::Gnome)
+                } else {
+                    None
+                };
+
+            if let Some(compositor) = compositor
+                && available(compositor)
+            {
+                return Some(compositor);
+            }
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = var;
+
+        Self::iter().find(|sw| !sw.is_compositor_specific() && available(*sw))
     }
 }
 
-fn switch(switcher_type: SwitcherType) -> Result<()> {
-    tracing::info!("Switching to {switcher_type:?}");
+/// Runs a command and returns its stdout, failing if it exited unsuccessfully
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn output(cmd: &mut Command) -> Result<String> {
+    tracing::debug!("Running {cmd:?}");
 
-    let Some(switcher) = Switcher::from_env() else {
-        tracing::error!("No supported focus switcher found, do nothing...");
-        return Ok(());
-    };
+    let output = cmd.output()?;
 
-    #[cfg(target_os = "linux")]
-    return match switcher {
-        Switcher::HyprCtl => {
-            Command::new(switcher.path().unwrap())
-                .arg("dispatch")
-                .arg("focuswindow")
-                .arg(match switcher_type {
-                    SwitcherType::Class(class) => format!("class:{class}"),
-                    SwitcherType::Title(title) => format!("title:{title}"),
-                    _ => bail!("Unsupported switcher type {switcher_type:?} for {switcher:?}"),
-                })
-                .spawn()?
-                .wait()?;
-
-            Ok(())
-        }
-        Switcher::SwayMsg => {
-            Command::new(switcher.path().unwrap())
-                .arg(format!(
-                    "[{}={}] focus",
-                    match switcher_type {
-                        SwitcherType::Class(_) => "class".to_string(),
-                        SwitcherType::Title(_) => "title".to_string(),
-                        _ => bail!("Unsupported switcher type {switcher_type:?} for {switcher:?}"),
-                    },
-                    switcher_type.value(),
-                ))
-                .spawn()?
-                .wait()?;
+    if !output.status.success() {
+        bail!(
+            "{:?} failed with {}: {}",
+            cmd.get_program(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
 
-            Ok(())
+/// i3/sway criteria matching the value exactly
+#[cfg(target_os = "linux")]
+fn criteria(switcher_type: &SwitcherType) -> Result<String> {
+    let key = match switcher_type {
+        SwitcherType::Class(_) => "class",
+        SwitcherType::Title(_) => "title",
+        SwitcherType::AppName(_) | SwitcherType::Pid(_) => {
+            bail!("Unsupported switcher type {switcher_type:?}")
         }
-        Switcher::WmCtrl => {
-            let mut cmd = Command::new(switcher.path().unwrap());
+    };
 
-            if matches!(switcher_type, SwitcherType::Class(_)) {
-                cmd.arg("-x");
-            }
+    Ok(format!(
+        "[{key}=\"^{}$\"] focus",
+        regex_escape(&switcher_type.value())
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn regex_escape(value: &str) -> String {
+    let mut pattern = String::new();
 
-            cmd.arg("-a").arg(switcher_type.value()).spawn()?.wait()?;
+    for c in value.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+
+    pattern
+}
 
-            Ok(())
+/// Window Calls returns its JSON as a GVariant string: `('[...]',)`
+#[cfg(target_os = "linux")]
+fn parse_gvariant_string(output: &str) -> Result<String> {
+    let inner = output
+        .trim()
+        .strip_prefix("('")
+        .and_then(|s| s.strip_suffix("',)"))
+        .with_context(|| format!("unexpected gdbus output: {output}"))?;
+
+    let mut result = String::new();
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\'
+            && let Some(escaped) = chars.next()
+        {
+            result.push(escaped);
+            continue;
         }
-        Switcher::XDoTool => {
-            Command::new(switcher.path().unwrap())
-                .arg("search")
-                .arg(match switcher_type {
-                    SwitcherType::Class(_) => "--class".to_string(),
-                    SwitcherType::Title(_) => "--title".to_string(),
-                    _ => bail!("Unsupported switcher type {switcher_type:?} for {switcher:?}"),
+
+        result.push(c);
+    }
+
+    Ok(result)
+}
+
+#[cfg(target_os = "linux")]
+const GNOME_WINDOWS_OBJECT: &str = "/org/gnome/Shell/Extensions/Windows";
+
+#[cfg(target_os = "linux")]
+const GNOME_WINDOWS_INTERFACE: &str = "org.gnome.Shell.Extensions.Windows";
+
+/// Runs a switcher command, a non-zero exit status means no matching window was found
+fn run_switcher(backend: &str, cmd: &mut Command) -> Result<FocusResult> {
+    tracing::debug!("Running {cmd:?}");
+
+    let output = cmd.output()?;
+
+    Ok(FocusResult {
+        backend: Some(backend.to_string()),
+        window_found: output.status.success(),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+impl Switcher {
+    fn focus(self, path: &Path, switcher_type: &SwitcherType) -> Result<FocusResult> {
+        let backend = self.executable_name();
+
+        #[cfg(target_os = "linux")]
+        return match self {
+            Switcher::HyprCtl => {
+                let mut cmd = Command::new(path);
+                cmd.arg("dispatch")
+                    .arg("focuswindow")
+                    .arg(match switcher_type {
+                        // both are regexes, anchored so that only the exact value matches
+                        SwitcherType::Class(class) => format!("class:^{}$", regex_escape(class)),
+                        SwitcherType::Title(title) => format!("title:^{}$", regex_escape(title)),
+                        SwitcherType::Pid(pid) => format!("pid:{pid}"),
+                        SwitcherType::AppName(_) => {
+                            bail!("Unsupported switcher type {switcher_type:?} for {self:?}")
+                        }
+                    });
+
+                tracing::debug!("Running {cmd:?}");
+                let output = cmd.output()?;
+
+                // hyprctl exits successfully either way and reports errors instead of "ok"
+                let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+                Ok(FocusResult {
+                    backend: Some(backend.to_string()),
+                    window_found: output.status.success() && stdout == "ok",
+                    stderr: if stderr.is_empty() && stdout != "ok" {
+                        stdout
+                    } else {
+                        stderr
+                    },
                 })
-                .arg(switcher_type.value())
-                .arg("windowactivate")
-                .spawn()?
-                .wait()?;
+            }
+            Switcher::SwayMsg => run_switcher(
+                backend,
+                Command::new(path).arg(match switcher_type {
+                    SwitcherType::Pid(pid) => format!("[pid={pid}] focus"),
+                    _ => criteria(switcher_type)?,
+                }),
+            ),
+            Switcher::WmCtrl => {
+                if let SwitcherType::Pid(pid) = switcher_type {
+                    // columns of -lp are: window id, desktop, pid, host, title
+                    let Some(window) =
+                        output(Command::new(path).arg("-lp"))?
+                            .lines()
+                            .find_map(|line| {
+                                let mut columns = line.split_whitespace();
+                                let id = columns.next()?;
+                                (columns.nth(1)? == pid.to_string()).then(|| id.to_string())
+                            })
+                    else {
+                        return Ok(FocusResult::not_found(backend));
+                    };
+
+                    return run_switcher(backend, Command::new(path).args(["-i", "-a", &window]));
+                }
+
+                let mut cmd = Command::new(path);
+
+                if matches!(switcher_type, SwitcherType::Class(_)) {
+                    cmd.arg("-x");
+                }
+
+                run_switcher(backend, cmd.arg("-a").arg(switcher_type.value()))
+            }
+            Switcher::XDoTool => run_switcher(
+                backend,
+                Command::new(path)
+                    .arg("search")
+                    .arg(match switcher_type {
+                        SwitcherType::Class(_) => "--class".to_string(),
+                        SwitcherType::Title(_) => "--title".to_string(),
+                        SwitcherType::Pid(_) => "--pid".to_string(),
+                        SwitcherType::AppName(_) => {
+                            bail!("Unsupported switcher type {switcher_type:?} for {self:?}")
+                        }
+                    })
+                    .arg(switcher_type.value())
+                    .arg("windowactivate"),
+            ),
+            Switcher::Niri => {
+                let key = match switcher_type {
+                    SwitcherType::Class(_) => "app_id",
+                    SwitcherType::Title(_) => "title",
+                    SwitcherType::Pid(_) => "pid",
+                    SwitcherType::AppName(_) => {
+                        bail!("Unsupported switcher type {switcher_type:?} for {self:?}")
+                    }
+                };
+
+                let windows: Vec<Value> = serde_json::from_str(&output(
+                    Command::new(path).args(["msg", "--json", "windows"]),
+                )?)?;
+
+                let Some(id) = windows
+                    .iter()
+                    .find(|window| switcher_type.matches(window, key))
+                    .and_then(|window| window["id"].as_u64())
+                else {
+                    return Ok(FocusResult::not_found(backend));
+                };
+
+                run_switcher(
+                    backend,
+                    Command::new(path)
+                        .args(["msg", "action", "focus-window", "--id"])
+                        .arg(id.to_string()),
+                )
+            }
+            Switcher::KDoTool => run_switcher(
+                backend,
+                Command::new(path)
+                    .arg("search")
+                    .arg(match switcher_type {
+                        SwitcherType::Class(_) => "--class",
+                        SwitcherType::Title(_) => "--name",
+                        SwitcherType::AppName(_) | SwitcherType::Pid(_) => {
+                            bail!("Unsupported switcher type {switcher_type:?} for {self:?}")
+                        }
+                    })
+                    .arg(switcher_type.value())
+                    .arg("windowactivate"),
+            ),
+            Switcher::I3Msg => {
+                run_switcher(backend, Command::new(path).arg(criteria(switcher_type)?))
+            }
+            Switcher::Gnome => {
+                let key = match switcher_type {
+                    SwitcherType::Class(_) => "wm_class",
+                    SwitcherType::Title(_) => "title",
+                    SwitcherType::Pid(_) => "pid",
+                    SwitcherType::AppName(_) => {
+                        bail!("Unsupported switcher type {switcher_type:?} for {self:?}")
+                    }
+                };
+
+                let gdbus = |method: &str| {
+                    let mut cmd = Command::new(path);
+                    cmd.args(["call", "--session", "--dest", "org.gnome.Shell"])
+                        .args(["--object-path", GNOME_WINDOWS_OBJECT])
+                        .arg("--method")
+                        .arg(format!("{GNOME_WINDOWS_INTERFACE}.{method}"));
+                    cmd
+                };
+
+                let windows: Vec<Value> = serde_json::from_str(&parse_gvariant_string(
+                    &output(&mut gdbus("List")).context(
+                        "Could not list windows, is the Window Calls extension installed?",
+                    )?,
+                )?)?;
+
+                let Some(id) = windows
+                    .iter()
+                    .find(|window| switcher_type.matches(window, key))
+                    .and_then(|window| window["id"].as_u64())
+                else {
+                    return Ok(FocusResult::not_found(backend));
+                };
+
+                run_switcher(backend, gdbus("Activate").arg(id.to_string()))
+            }
+        };
 
-            Ok(())
+        #[cfg(target_os = "macos")]
+        return match self {
+            Switcher::OsaScript => run_switcher(
+                backend,
+                Command::new(path)
+                    .arg("-e")
+                    .arg(match switcher_type {
+                        SwitcherType::AppName(app_name) => format!(
+                            "tell application \"System Events\" to tell process \"{}\" to set frontmost to true",
+                            app_name.replace('\\', "\\\\").replace('"', "\\\"")
+                        ),
+                        SwitcherType::Pid(pid) => format!("tell application \"System Events\" to set frontmost of (first process whose unix id is {pid}) to true"),
+                        _ => bail!("Unsupported switcher type {switcher_type:?} for {self:?}"),
+                    }),
+            ),
+        };
+
+        #[cfg(target_os = "windows")]
+        {
+            let _ = (path, switcher_type, backend);
+            match self {}
         }
+    }
+}
+
+/// Fills in the placeholder matching `switcher_type`, the other ones are left empty
+fn expand_command(template: &[String], switcher_type: &SwitcherType) -> Result<Vec<String>> {
+    let placeholder = match switcher_type {
+        SwitcherType::Class(_) => "{class}",
+        SwitcherType::Title(_) | SwitcherType::AppName(_) => "{title}",
+        SwitcherType::Pid(_) => "{pid}",
     };
 
-    #[cfg(target_os = "macos")]
-    return match switcher {
-        Switcher::OsaScript => {
-            Command::new(switcher.path().unwrap())
-                .arg("-e")
-                .arg(match switcher_type {
-                    SwitcherType::AppName(app_name) => format!("'tell application \"System Events\" to tell process \"{app_name}\" to set frontmost to true'"),
-                    _ => bail!("Unsupported switcher type {switcher_type:?} for {switcher:?}"),
+    if !template.iter().any(|arg| arg.contains(placeholder)) {
+        bail!("Custom focus command {template:?} has no {placeholder} placeholder");
+    }
+
+    Ok(template
+        .iter()
+        .map(|arg| {
+            ["{class}", "{title}", "{pid}"]
+                .iter()
+                .fold(arg.clone(), |arg, other| {
+                    if *other == placeholder {
+                        arg.replace(other, &switcher_type.value())
+                    } else {
+                        arg.replace(other, "")
+                    }
                 })
-                .spawn()?
-                .wait()?;
+        })
+        .collect())
+}
 
-            Ok(())
-        }
+fn run_custom(template: &[String], switcher_type: &SwitcherType) -> Result<FocusResult> {
+    let command = expand_command(template, switcher_type)?;
+    let (program, args) = command
+        .split_first()
+        .context("custom focus command is empty")?;
+
+    run_switcher("custom", Command::new(program).args(args))
+}
+
+fn switch(settings: &FocusSettings, switcher_type: SwitcherType) -> Result<FocusResult> {
+    tracing::info!("Switching to {switcher_type:?}");
+
+    let result = if let Some(command) = &settings.command {
+        run_custom(command, &switcher_type)?
+    } else {
+        let switcher = match settings.switcher {
+            Some(switcher) => switcher,
+            None => {
+                let Some(switcher) = Switcher::from_env() else {
+                    tracing::error!("No supported focus switcher found, do nothing...");
+                    return Ok(FocusResult::default());
+                };
+
+                switcher
+            }
+        };
+
+        let path = switcher.path().with_context(|| {
+            format!(
+                "Configured focus switcher {} could not be found",
+                switcher.executable_name()
+            )
+        })?;
+
+        switcher.focus(&path, &switcher_type)?
     };
 
-    #[cfg(target_os = "windows")]
-    return Ok(());
+    if !result.window_found {
+        tracing::warn!("No window found for {switcher_type:?}: {result:?}");
+    }
+
+    Ok(result)
+}
+
+/// Only the global config fills in unset settings, a checked in project config must never
+/// decide which command gets run
+fn global_settings(settings: &FocusSettings) -> Result<FocusSettings> {
+    Ok(settings.clone().or_settings(config::load(None)?.focus))
 }
 
-pub fn focus_neovim(root_dir: PathBuf) -> Result<()> {
+pub fn focus_neovim(root_dir: PathBuf, settings: &FocusSettings) -> Result<FocusResult> {
     if !root_dir.join("game.project").exists() {
         bail!("Could not find game.project file in {root_dir:?}: Not a valid Defold directory");
     }
 
+    // Neovim running inside a multiplexer pane. The window hosting the multiplexer isn't ours,
+    // so switching by class wouldn't find anything in that case
+    if let Some(pane) = multiplexer::find_pane(&root_dir) {
+        pane.focus()?;
+
+        return Ok(FocusResult {
+            backend: Some(pane.multiplexer.executable_name().to_string()),
+            window_found: true,
+            stderr: String::new(),
+        });
+    }
+
+    let settings = global_settings(settings)?;
+
     if cfg!(target_os = "linux") {
-        let class = classname(
-            root_dir
-                .to_str()
-                .context("could not convert path to string")?,
-        )?;
+        let class = ProjectId::from_root(&root_dir)?.classname();
 
-        return switch(SwitcherType::Class(class));
+        return switch(&settings, SwitcherType::Class(class));
     }
 
     tracing::error!("Focus switching to Neovim is not support on current platform");
 
-    Ok(())
+    Ok(FocusResult::default())
 }
 
-pub fn focus_game(root_dir: PathBuf) -> Result<()> {
+pub fn focus_game(root_dir: PathBuf, settings: &FocusSettings) -> Result<FocusResult> {
     if !root_dir.join("game.project").exists() {
         bail!("Could not find game.project file in {root_dir:?}: Not a valid Defold directory");
     }
 
     let game_project = GameProject::load_from_path(&root_dir.join("game.project"))?;
+    let settings = global_settings(settings)?;
+
+    // the title is ambiguous with multiple builds running, so prefer the engine process
+    if let Some(pid) = engine::find_pid(&root_dir) {
+        match switch(&settings, SwitcherType::Pid(pid)) {
+            Ok(result) if result.window_found => return Ok(result),
+            Ok(result) => {
+                tracing::debug!(
+                    "Could not focus game by pid {pid}, using title instead: {result:?}"
+                );
+            }
+            Err(err) => {
+                tracing::debug!("Could not focus game by pid {pid}, using title instead: {err:?}");
+            }
+        }
+    }
 
     if cfg!(target_os = "linux") {
-        return switch(SwitcherType::Title(game_project.title));
+        return switch(&settings, SwitcherType::Title(game_project.title));
     } else if cfg!(target_os = "macos") {
-        return switch(SwitcherType::AppName(game_project.title));
+        return switch(&settings, SwitcherType::AppName(game_project.title));
     }
 
     tracing::error!("Focus switching to the Game is not support on current platform");
 
-    Ok(())
+    Ok(FocusResult::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::focus::{SwitcherType, expand_command};
+    use pretty_assertions::assert_eq;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_detect_prefers_running_compositor() {
+        use crate::focus::Switcher;
+        use strum::IntoEnumIterator;
+
+        let detect = |vars: &[(&str, &str)], available: &[Switcher]| {
+            Switcher::detect(
+                |name| {
+                    vars.iter()
+                        .find(|(k, _)| *k == name)
+                        .map(|(_, v)| (*v).to_string())
+                },
+                |sw| available.contains(&sw),
+            )
+        };
+
+        let all = Switcher::iter().collect::<Vec<_>>();
+
+        assert_eq!(
+            Some(Switcher::HyprCtl),
+            detect(&[("HYPRLAND_INSTANCE_SIGNATURE", "abc")], &all)
+        );
+        assert_eq!(
+            Some(Switcher::SwayMsg),
+            detect(&[("SWAYSOCK", "/run/sway.sock")], &all)
+        );
+        assert_eq!(
+            Some(Switcher::SwayMsg),
+            detect(&[("XDG_CURRENT_DESKTOP", "sway")], &all)
+        );
+        assert_eq!(
+            Some(Switcher::Niri),
+            detect(&[("NIRI_SOCKET", "/run/niri.sock")], &all)
+        );
+        assert_eq!(
+            Some(Switcher::KDoTool),
+            detect(&[("XDG_CURRENT_DESKTOP", "KDE")], &all)
+        );
+        assert_eq!(
+            Some(Switcher::Gnome),
+            detect(&[("XDG_CURRENT_DESKTOP", "ubuntu:GNOME")], &all)
+        );
+        assert_eq!(Some(Switcher::WmCtrl), detect(&[], &all));
+        assert_eq!(
+            Some(Switcher::XDoTool),
+            detect(
+                &[("HYPRLAND_INSTANCE_SIGNATURE", "abc")],
+                &[Switcher::SwayMsg, Switcher::XDoTool]
+            )
+        );
+        assert_eq!(None, detect(&[], &[Switcher::HyprCtl]));
+    }
+
+    #[test]
+    fn test_expand_command() {
+        let template = vec![
+            "focus-window".to_string(),
+            "--match={class}{title}".to_string(),
+        ];
+
+        assert_eq!(
+            vec!["focus-window", "--match=com.defold.nvim.1234"],
+            expand_command(
+                &template,
+                &SwitcherType::Class("com.defold.nvim.1234".into())
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            vec!["focus-window", "--match=My Game"],
+            expand_command(&template, &SwitcherType::Title("My Game".into())).unwrap()
+        );
+        assert!(
+            expand_command(
+                &["focus".to_string(), "{class}".to_string()],
+                &SwitcherType::Title("My Game".into())
+            )
+            .is_err()
+        );
+    }
+
+    /// Creates a fake executable that records its arguments and prints `name.out` when asked
+    /// for the window list
+    #[cfg(target_os = "linux")]
+    fn fake_binary(dir: &std::path::Path, name: &str, out: Option<&str>) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        let log = dir.join(format!("{name}.log"));
+
+        std::fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\nfor arg in \"$@\"; do printf '[%s]' \"$arg\"; done >> '{}'\necho >> '{}'\ncase \"$*\" in *--json*|*List*|-lp) cat \"$0.out\";; esac\n",
+                log.display(),
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        if let Some(out) = out {
+            std::fs::write(dir.join(format!("{name}.out")), out).unwrap();
+        }
+
+        which::which_in(name, Some(dir), dir).unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_focus_backends_with_fake_binaries() {
+        use crate::focus::Switcher;
+
+        let dir = std::env::temp_dir().join(format!("defold-nvim-focus-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let class = SwitcherType::Class("com.defold.nvim.1234".into());
+        let title = SwitcherType::Title("Tom's (Game)".into());
+
+        let log = |name: &str| std::fs::read_to_string(dir.join(format!("{name}.log"))).unwrap();
+
+        let niri = fake_binary(
+            &dir,
+            "niri",
+            Some(
+                r#"[{"id": 7, "pid": 100, "app_id": "kitty", "title": "x"}, {"id": 42, "pid": 1234, "app_id": "com.defold.nvim.1234", "title": "Tom's (Game)"}]"#,
+            ),
+        );
+        Switcher::Niri.focus(&niri, &class).unwrap();
+        assert_eq!(
+            "[msg][--json][windows]\n[msg][action][focus-window][--id][42]\n",
+            log("niri")
+        );
+
+        Switcher::Niri
+            .focus(&niri, &SwitcherType::Pid(100))
+            .unwrap();
+        assert!(log("niri").ends_with("[msg][action][focus-window][--id][7]\n"));
+
+        let wmctrl = fake_binary(
+            &dir,
+            "wmctrl",
+            Some("0x01000003  0 4242   host kitty\n0x04a00002  0 1234   host Tom's (Game)\n"),
+        );
+        Switcher::WmCtrl
+            .focus(&wmctrl, &SwitcherType::Pid(1234))
+            .unwrap();
+        assert_eq!("[-lp]\n[-i][-a][0x04a00002]\n", log("wmctrl"));
+
+        let kdotool = fake_binary(&dir, "kdotool", None);
+        Switcher::KDoTool.focus(&kdotool, &class).unwrap();
+        Switcher::KDoTool.focus(&kdotool, &title).unwrap();
+        assert_eq!(
+            "[search][--class][com.defold.nvim.1234][windowactivate]\n[search][--name][Tom's (Game)][windowactivate]\n",
+            log("kdotool")
+        );
+
+        let hyprctl = fake_binary(&dir, "hyprctl", None);
+        Switcher::HyprCtl.focus(&hyprctl, &class).unwrap();
+        Switcher::HyprCtl.focus(&hyprctl, &title).unwrap();
+        assert_eq!(
+            "[dispatch][focuswindow][class:^com\\.defold\\.nvim\\.1234$]\n[dispatch][focuswindow][title:^Tom's \\(Game\\)$]\n",
+            log("hyprctl")
+        );
+
+        let i3 = fake_binary(&dir, "i3-msg", None);
+        Switcher::I3Msg.focus(&i3, &class).unwrap();
+        Switcher::I3Msg.focus(&i3, &title).unwrap();
+        assert_eq!(
+            "[[class=\"^com\\\\.defold\\\\.nvim\\\\.1234$\"] focus]\n[[title=\"^Tom's \\\\(Game\\\\)$\"] focus]\n",
+            log("i3-msg")
+        );
+
+        let sway = fake_binary(&dir, "swaymsg", None);
+        Switcher::SwayMsg.focus(&sway, &title).unwrap();
+        Switcher::SwayMsg
+            .focus(&sway, &SwitcherType::Pid(1234))
+            .unwrap();
+        assert_eq!(
+            "[[title=\"^Tom's \\\\(Game\\\\)$\"] focus]\n[[pid=1234] focus]\n",
+            log("swaymsg")
+        );
+
+        let gdbus = fake_binary(
+            &dir,
+            "gdbus",
+            Some(
+                r#"('[{"id": 3, "wm_class": "kitty", "title": "x"}, {"id": 99, "wm_class": "dmengine", "title": "Tom\'s (Game)"}]',)"#,
+            ),
+        );
+        Switcher::Gnome.focus(&gdbus, &title).unwrap();
+        assert_eq!(
+            "[call][--session][--dest][org.gnome.Shell][--object-path][/org/gnome/Shell/Extensions/Windows][--method][org.gnome.Shell.Extensions.Windows.List]\n\
+             [call][--session][--dest][org.gnome.Shell][--object-path][/org/gnome/Shell/Extensions/Windows][--method][org.gnome.Shell.Extensions.Windows.Activate][99]\n",
+            log("gdbus")
+        );
+
+        assert!(!Switcher::Gnome.focus(&gdbus, &class).unwrap().window_found);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_focus_result_reports_failures() {
+        use crate::focus::Switcher;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir =
+            std::env::temp_dir().join(format!("defold-nvim-focus-result-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let script = |name: &str, body: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+
+        let class = SwitcherType::Class("com.defold.nvim.1234".into());
+
+        let xdotool = script("xdotool", "echo 'Cannot open display' >&2; exit 1");
+        let result = Switcher::XDoTool.focus(&xdotool, &class).unwrap();
+        assert_eq!(Some("xdotool".to_string()), result.backend);
+        assert!(!result.window_found);
+        assert_eq!("Cannot open display", result.stderr);
+
+        // hyprctl doesn't use its exit status to report errors
+        let hyprctl = script("hyprctl", "echo 'No such window found'");
+        let result = Switcher::HyprCtl.focus(&hyprctl, &class).unwrap();
+        assert!(!result.window_found);
+        assert_eq!("No such window found", result.stderr);
+
+        let hyprctl = script("hyprctl", "echo ok");
+        assert!(
+            Switcher::HyprCtl
+                .focus(&hyprctl, &class)
+                .unwrap()
+                .window_found
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -1,20 +1,124 @@
 use std::{
-    env::temp_dir,
-    fs::{self},
-    path::PathBuf,
+    env::{self, temp_dir},
+    fmt,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
 };
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
+use reqwest::blocking::Response;
 use serde::{Deserialize, Serialize};
+use url::Url;
 
-use crate::{cache, utils};
+use crate::{cache, config, offline, utils};
 
 const USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/143.0.0.0 Safari/537.36";
 
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct GithubSettings {
+    /// Base URL of the API, e.g. `https://github.example.com/api/v3`
+    pub api_url: Option<String>,
+
+    /// Base URL release assets are downloaded from instead of the host GitHub reports
+    pub download_url: Option<String>,
+
+    /// Token for API requests, `GITHUB_TOKEN` takes precedence
+    pub token: Option<String>,
+
+    /// Install assets even if the release doesn't publish a checksum for them
+    #[serde(default)]
+    pub allow_unverified: bool,
+}
+
+impl fmt::Debug for GithubSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GithubSettings")
+            .field("api_url", &self.api_url)
+            .field("download_url", &self.download_url)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("allow_unverified", &self.allow_unverified)
+            .finish()
+    }
+}
+
+impl GithubSettings {
+    #[must_use]
+    pub fn load() -> Self {
+        let mut settings = match config::load(None) {
+            Ok(config) => config.github,
+            Err(err) => {
+                tracing::error!("Could not load config, using default GitHub settings: {err:?}");
+                Self::default()
+            }
+        };
+
+        if let Ok(token) = env::var("GITHUB_TOKEN")
+            && !token.is_empty()
+        {
+            settings.token = Some(token);
+        }
+
+        settings
+    }
+
+    fn api_url(&self) -> &str {
+        self.api_url
+            .as_deref()
+            .unwrap_or(DEFAULT_API_URL)
+            .trim_end_matches('/')
+    }
+
+    /// Points asset urls to the mirror, if there is one
+    fn asset_url(&self, url: &str) -> Result<String> {
+        let Some(base) = &self.download_url else {
+            return Ok(url.to_string());
+        };
+
+        let url = Url::parse(url)?;
+
+        Ok(format!("{}{}", base.trim_end_matches('/'), url.path()))
+    }
+
+    /// The token is only sent to GitHub itself, never to a mirror
+    fn get(&self, url: &str, authenticated: bool) -> Result<Response> {
+        offline::ensure_online(url)?;
+
+        let mut req = reqwest::blocking::Client::new()
+            .get(url)
+            .header("User-Agent", USER_AGENT);
+
+        if authenticated && let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+
+        let res = req.send()?;
+
+        if let Err(err) = res.error_for_status_ref() {
+            bail!(
+                "Github: Request to {url} failed: {err} {}",
+                res.text().unwrap_or_default().trim()
+            );
+        }
+
+        Ok(res)
+    }
+
+    fn get_asset(&self, url: &str) -> Result<Response> {
+        self.get(&self.asset_url(url)?, self.download_url.is_none())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Asset {
     pub name: String,
     pub browser_download_url: String,
+
+    /// e.g. `sha256:<hex>`, only set for assets uploaded after GitHub started computing them
+    #[serde(default)]
+    pub digest: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,10 +127,28 @@ pub struct Release {
     pub assets: Vec<Asset>,
 }
 
-pub fn fetch_release(owner: &str, repo: &str) -> Result<Release> {
-    let url = format!("https://api.github.com/repos/{owner}/{repo}/releases/latest");
+/// Fetches the release tagged `tag`, or the latest one
+pub fn fetch_release(owner: &str, repo: &str, tag: Option<&str>) -> Result<Release> {
+    fetch_release_with(&GithubSettings::load(), owner, repo, tag)
+}
+
+fn fetch_release_with(
+    settings: &GithubSettings,
+    owner: &str,
+    repo: &str,
+    tag: Option<&str>,
+) -> Result<Release> {
+    let release_path = match tag {
+        Some(tag) => format!("tags/{tag}"),
+        None => "latest".to_string(),
+    };
+
+    let url = format!(
+        "{}/repos/{owner}/{repo}/releases/{release_path}",
+        settings.api_url()
+    );
 
-    tracing::debug!("Github: Fetching release {owner}/{repo}");
+    tracing::debug!("Github: Fetching release {owner}/{repo} ({release_path})");
 
     if let Some(str) = cache::get(&url)
         && let Ok(res) = serde_json::from_str::<Release>(&str)
@@ -35,10 +157,7 @@ pub fn fetch_release(owner: &str, repo: &str) -> Result<Release> {
         return Ok(res);
     }
 
-    let client = reqwest::blocking::Client::new();
-    let res = client.get(&url).header("User-Agent", USER_AGENT).send()?;
-
-    let release: Release = res.json()?;
+    let release: Release = settings.get(&url, true)?.json()?;
 
     tracing::debug!("Github: Found {}", release.tag_name);
 
@@ -47,6 +166,93 @@ pub fn fetch_release(owner: &str, repo: &str) -> Result<Release> {
     Ok(release)
 }
 
+/// Names of release assets listing the checksums of all other assets
+const CHECKSUM_LISTS: [&str; 4] = [
+    "SHA256SUMS",
+    "sha256sums.txt",
+    "checksums.txt",
+    "SHA256SUMS.txt",
+];
+
+fn parse_sha256(hex: &str) -> Result<String> {
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Invalid SHA-256 digest '{hex}'");
+    }
+
+    Ok(hex.to_lowercase())
+}
+
+/// Finds the digest for `name` in a `sha256sum` style listing
+fn checksum_from_listing(listing: &str, name: &str) -> Option<String> {
+    listing.lines().find_map(|line| {
+        let (hex, file) = line.trim().split_once(char::is_whitespace)?;
+        let file = file.trim().trim_start_matches('*');
+
+        (file == name).then(|| parse_sha256(hex).ok()).flatten()
+    })
+}
+
+/// Digest published for `asset`, either by GitHub itself or as a checksum asset of the release
+fn expected_sha256(
+    settings: &GithubSettings,
+    release: &Release,
+    asset: &Asset,
+) -> Result<Option<String>> {
+    if let Some(digest) = &asset.digest
+        && let Some(hex) = digest.strip_prefix("sha256:")
+    {
+        return parse_sha256(hex).map(Some);
+    }
+
+    let checksum_names = [
+        format!("{}.sha256", asset.name),
+        format!("{}.sha256sum", asset.name),
+    ];
+
+    if let Some(checksum_asset) = release
+        .assets
+        .iter()
+        .find(|a| checksum_names.contains(&a.name))
+    {
+        let content = settings
+            .get_asset(&checksum_asset.browser_download_url)?
+            .text()?;
+        let hex = content
+            .split_whitespace()
+            .next()
+            .with_context(|| format!("{} is empty", checksum_asset.name))?;
+
+        return parse_sha256(hex).map(Some);
+    }
+
+    if let Some(listing) = release
+        .assets
+        .iter()
+        .find(|a| CHECKSUM_LISTS.contains(&a.name.as_str()))
+    {
+        let content = settings.get_asset(&listing.browser_download_url)?.text()?;
+
+        return checksum_from_listing(&content, &asset.name)
+            .with_context(|| format!("{} has no entry for {}", listing.name, asset.name))
+            .map(Some);
+    }
+
+    Ok(None)
+}
+
+fn verify_sha256(path: &Path, expected: &str) -> Result<()> {
+    let actual = utils::sha256_file(path)?;
+
+    if actual != expected {
+        bail!(
+            "Checksum mismatch for {}: expected {expected}, got {actual}",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
 pub fn clear_downloads(owner: &str, repo: &str) -> Result<()> {
     let temp = temp_dir()
         .join("defold.nvim")
@@ -61,11 +267,27 @@ pub fn clear_downloads(owner: &str, repo: &str) -> Result<()> {
     Ok(())
 }
 
+/// Downloads the first asset `matching` matches, along with its sha256 if it could be verified
+/// against a published checksum
 pub fn download_release_matching<F>(
     owner: &str,
     repo: &str,
+    tag: Option<&str>,
     matching: F,
-) -> Result<(PathBuf, Release)>
+) -> Result<(PathBuf, Release, Option<String>)>
+where
+    F: Fn(&Asset) -> bool,
+{
+    download_release_matching_with(&GithubSettings::load(), owner, repo, tag, matching)
+}
+
+fn download_release_matching_with<F>(
+    settings: &GithubSettings,
+    owner: &str,
+    repo: &str,
+    tag: Option<&str>,
+    matching: F,
+) -> Result<(PathBuf, Release, Option<String>)>
 where
     F: Fn(&Asset) -> bool,
 {
@@ -76,7 +298,7 @@ where
         .join(repo);
     fs::create_dir_all(&temp)?;
 
-    let release = fetch_release(owner, repo)?;
+    let release = fetch_release_with(settings, owner, repo, tag)?;
 
     let Some(asset) = release.assets.iter().find(|asset| matching(asset)) else {
         bail!("Could not find asset for {owner}/{repo}");
@@ -84,17 +306,195 @@ where
 
     let download_file = temp.join(&asset.name);
 
+    let expected = expected_sha256(settings, &release, asset)?;
+
+    if expected.is_none() {
+        if !settings.allow_unverified {
+            bail!(
+                "No checksum published for {}, set `github.allow_unverified` to install it anyway",
+                asset.name
+            );
+        }
+
+        tracing::warn!(
+            "Github: No checksum published for {}, can't verify it",
+            asset.name
+        );
+    }
+
     if download_file.exists() {
-        return Ok((download_file, release));
+        match &expected {
+            Some(expected) if verify_sha256(&download_file, expected).is_err() => {
+                tracing::debug!("Github: Discarding previous download {}", asset.name);
+                fs::remove_file(&download_file)?;
+            }
+            _ => return Ok((download_file, release, expected)),
+        }
     }
 
-    utils::download_to(&asset.browser_download_url, &download_file)?;
+    tracing::debug!(
+        "Github: Downloading {} to {}...",
+        asset.name,
+        download_file.display()
+    );
+
+    let mut res = settings.get_asset(&asset.browser_download_url)?;
+    io::copy(&mut res, &mut File::create(&download_file)?)?;
+
+    if let Some(expected) = &expected
+        && let Err(err) = verify_sha256(&download_file, expected)
+    {
+        fs::remove_file(&download_file)?;
+        return Err(err);
+    }
 
-    Ok((download_file, release))
+    Ok((download_file, release, expected))
 }
 
-pub fn download_release(owner: &str, repo: &str, name: &str) -> Result<(PathBuf, Release)> {
-    download_release_matching(owner, repo, |asset| asset.name == name).map_err(|err| {
-        anyhow::anyhow!("Could not find asset {name} for {owner}/{repo} because: {err:?}")
+/// Downloads the asset matching `pattern`, where `*` matches anything
+pub fn download_release(
+    owner: &str,
+    repo: &str,
+    tag: Option<&str>,
+    pattern: &str,
+) -> Result<(PathBuf, Release, Option<String>)> {
+    download_release_matching(owner, repo, tag, |asset| {
+        utils::glob_match(pattern, &asset.name)
     })
+    .map_err(|err| {
+        anyhow::anyhow!("Could not find asset {pattern} for {owner}/{repo} because: {err:?}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        github::{
+            GithubSettings, checksum_from_listing, clear_downloads, download_release_matching_with,
+            verify_sha256,
+        },
+        test_utils::serve_routes,
+    };
+    use pretty_assertions::assert_eq;
+    use std::{env, fs};
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_checksum_from_listing() {
+        let listing = format!(
+            "{}  neovide.AppImage\n{HELLO_SHA256} *mobdap-linux-amd64.tar.gz\n",
+            "0".repeat(64)
+        );
+
+        assert_eq!(
+            Some(HELLO_SHA256.to_string()),
+            checksum_from_listing(&listing, "mobdap-linux-amd64.tar.gz")
+        );
+        assert_eq!(
+            None,
+            checksum_from_listing(&listing, "mobdap-linux-arm64.tar.gz")
+        );
+    }
+
+    #[test]
+    fn test_verify_sha256() {
+        let file = env::temp_dir().join(format!("defold-nvim-sha256-{}", std::process::id()));
+        fs::write(&file, "hello").unwrap();
+
+        assert!(verify_sha256(&file, HELLO_SHA256).is_ok());
+        assert!(verify_sha256(&file, &"0".repeat(64)).is_err());
+
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_download_from_configured_hosts() {
+        let owner = format!("owner-{}", std::process::id());
+
+        let release = format!(
+            r#"{{"tag_name": "v1.0.0", "assets": [{{"name": "tool.tar.gz", "browser_download_url": "https://github.com/{owner}/tool/releases/download/v1.0.0/tool.tar.gz", "digest": "sha256:{HELLO_SHA256}"}}]}}"#
+        );
+
+        let (api, api_requests) = serve_routes(vec![(
+            format!("/api/v3/repos/{owner}/tool/releases/tags/v1.0.0"),
+            release,
+        )]);
+        let (mirror, mirror_requests) = serve_routes(vec![(
+            format!("/{owner}/tool/releases/download/v1.0.0/tool.tar.gz"),
+            "hello".to_string(),
+        )]);
+
+        let settings = GithubSettings {
+            api_url: Some(format!("{api}/api/v3/")),
+            download_url: Some(mirror),
+            token: Some("secret".to_string()),
+            ..Default::default()
+        };
+
+        let (path, release, sha256) =
+            download_release_matching_with(&settings, &owner, "tool", Some("v1.0.0"), |asset| {
+                asset.name == "tool.tar.gz"
+            })
+            .unwrap();
+
+        assert_eq!("v1.0.0", release.tag_name);
+        assert_eq!("hello", fs::read_to_string(&path).unwrap());
+        assert_eq!(Some(HELLO_SHA256.to_string()), sha256);
+
+        let api_requests = api_requests.lock().unwrap();
+        assert_eq!(1, api_requests.len());
+        assert_eq!(
+            Some("bearer secret"),
+            api_requests[0].header("authorization")
+        );
+
+        let mirror_requests = mirror_requests.lock().unwrap();
+        assert_eq!(1, mirror_requests.len());
+        assert_eq!(None, mirror_requests[0].header("authorization"));
+
+        clear_downloads(&owner, "tool").unwrap();
+    }
+
+    #[test]
+    fn test_unverified_download_needs_opt_in() {
+        let owner = format!("unverified-owner-{}", std::process::id());
+
+        let release = format!(
+            r#"{{"tag_name": "v1.0.0", "assets": [{{"name": "tool.tar.gz", "browser_download_url": "https://github.com/{owner}/tool/releases/download/v1.0.0/tool.tar.gz"}}]}}"#
+        );
+
+        let (api, _) = serve_routes(vec![(
+            format!("/repos/{owner}/tool/releases/tags/v1.0.0"),
+            release,
+        )]);
+        let (mirror, mirror_requests) = serve_routes(vec![(
+            format!("/{owner}/tool/releases/download/v1.0.0/tool.tar.gz"),
+            "hello".to_string(),
+        )]);
+
+        let mut settings = GithubSettings {
+            api_url: Some(api),
+            download_url: Some(mirror),
+            ..Default::default()
+        };
+
+        let download = |settings: &GithubSettings| {
+            download_release_matching_with(settings, &owner, "tool", Some("v1.0.0"), |asset| {
+                asset.name == "tool.tar.gz"
+            })
+        };
+
+        let err = download(&settings).unwrap_err();
+        assert!(err.to_string().contains("allow_unverified"), "{err:?}");
+        assert!(mirror_requests.lock().unwrap().is_empty());
+
+        settings.allow_unverified = true;
+
+        let (path, _, sha256) = download(&settings).unwrap();
+        assert_eq!("hello", fs::read_to_string(&path).unwrap());
+        assert_eq!(None, sha256);
+
+        clear_downloads(&owner, "tool").unwrap();
+    }
 }
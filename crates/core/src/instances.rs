@@ -0,0 +1,366 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use crate::nvim_server;
+
+const REGISTRY_FILE: &str = "instance.json";
+
+/// Runtime dirs are created a moment before the server in them comes up
+const PRUNE_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// A running Neovim instance serving a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Instance {
+    pub pid: u32,
+    pub address: String,
+    pub launcher: String,
+    pub root_dir: PathBuf,
+}
+
+impl Instance {
+    #[must_use]
+    pub fn is_alive(&self) -> bool {
+        nvim_server::is_server_reachable(&self.address)
+    }
+}
+
+pub fn register(instance: &Instance) -> Result<()> {
+    register_in(&nvim_server::runtime_root()?, instance)
+}
+
+fn register_in(runtime_root: &Path, instance: &Instance) -> Result<()> {
+    let file = nvim_server::runtime_dir_in(runtime_root, &instance.root_dir)?.join(REGISTRY_FILE);
+
+    tracing::debug!("Registering {instance:?}");
+
+    fs::write(file, serde_json::to_string(instance)?)?;
+
+    Ok(())
+}
+
+fn read(runtime_dir: &Path) -> Option<Instance> {
+    let content = fs::read_to_string(runtime_dir.join(REGISTRY_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Also covers instances which were started before they got registered
+fn has_live_server(runtime_dir: &Path) -> bool {
+    if read(runtime_dir).is_some_and(|instance| instance.is_alive()) {
+        return true;
+    }
+
+    let socket = runtime_dir.join("neovim.sock");
+
+    if socket.exists()
+        && let Some(addr) = socket.to_str()
+        && nvim_server::is_server_reachable(addr)
+    {
+        return true;
+    }
+
+    fs::read_to_string(runtime_dir.join("port"))
+        .is_ok_and(|port| nvim_server::is_server_reachable(&format!("127.0.0.1:{}", port.trim())))
+}
+
+fn is_locked(runtime_dir: &Path) -> bool {
+    let Ok(file) = OpenOptions::new()
+        .write(true)
+        .open(runtime_dir.join("launch.lock"))
+    else {
+        return false;
+    };
+
+    file.try_lock().is_err()
+}
+
+fn is_recent(dir: &Path) -> bool {
+    fs::metadata(dir)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age < PRUNE_GRACE_PERIOD)
+        })
+}
+
+/// Removes the registry entries and sockets of instances that aren't running anymore. Runtime
+/// dirs without a live instance are deleted entirely, unless a launch is in progress there or
+/// they were only just created
+pub fn prune() -> Result<()> {
+    prune_in(&nvim_server::runtime_root()?)
+}
+
+fn prune_in(runtime_root: &Path) -> Result<()> {
+    if !runtime_root.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(runtime_root)? {
+        let dir = entry?.path();
+
+        if !dir.is_dir() {
+            continue;
+        }
+
+        if has_live_server(&dir) || is_locked(&dir) || is_recent(&dir) {
+            continue;
+        }
+
+        tracing::debug!("Pruning runtime dir {}", dir.display());
+
+        if let Err(err) = fs::remove_dir_all(&dir) {
+            tracing::error!("Could not prune {}: {err:?}", dir.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists all live instances along with their project id
+pub fn list() -> Result<Vec<(String, Instance)>> {
+    list_in(&nvim_server::runtime_root()?)
+}
+
+fn list_in(runtime_root: &Path) -> Result<Vec<(String, Instance)>> {
+    prune_in(runtime_root)?;
+
+    if !runtime_root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut instances = Vec::new();
+
+    for entry in fs::read_dir(runtime_root)? {
+        let dir = entry?.path();
+
+        let Some(project_id) = dir.file_name().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        // dirs with a launch in progress don't get pruned, so the entry could still be dead
+        if let Some(instance) = read(&dir)
+            && instance.is_alive()
+        {
+            instances.push((project_id.to_string(), instance));
+        }
+    }
+
+    instances.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(instances)
+}
+
+pub fn kill(project_id: &str) -> Result<Instance> {
+    kill_in(&nvim_server::runtime_root()?, project_id)
+}
+
+fn kill_in(runtime_root: &Path, project_id: &str) -> Result<Instance> {
+    let dir = runtime_root.join(project_id);
+
+    let instance = read(&dir).with_context(|| format!("No instance found for {project_id}"))?;
+
+    // once Neovim is gone its pid may already belong to some other process
+    if !instance.is_alive() {
+        fs::remove_file(dir.join(REGISTRY_FILE))?;
+        bail!("Instance of {project_id} is not running anymore");
+    }
+
+    tracing::info!("Killing {instance:?}");
+
+    let status = if cfg!(target_os = "windows") {
+        Command::new("taskkill")
+            .arg("/PID")
+            .arg(instance.pid.to_string())
+            .arg("/F")
+            .status()?
+    } else {
+        Command::new("kill")
+            .arg(instance.pid.to_string())
+            .status()?
+    };
+
+    if !status.success() {
+        bail!("Could not kill process {}: {status}", instance.pid);
+    }
+
+    fs::remove_file(dir.join(REGISTRY_FILE))?;
+
+    let socket = Path::new(&instance.address);
+    if socket.is_absolute() && socket.exists() {
+        fs::remove_file(socket)?;
+    }
+
+    Ok(instance)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        instances::{Instance, REGISTRY_FILE, kill_in, list_in, prune_in, read, register_in},
+        nvim_server,
+    };
+    use pretty_assertions::assert_eq;
+    use std::{
+        env,
+        fs::{self, File, OpenOptions},
+        net::TcpListener,
+        path::{Path, PathBuf},
+        time::{Duration, SystemTime},
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("instances-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn instance(address: &str, pid: u32) -> Instance {
+        Instance {
+            pid,
+            address: address.to_string(),
+            launcher: "kitty".to_string(),
+            root_dir: PathBuf::from("/my/game"),
+        }
+    }
+
+    /// Creates a runtime dir with a registered instance, old enough to be pruned
+    fn runtime_dir(runtime_root: &Path, project_id: &str, instance: &Instance) -> PathBuf {
+        let dir = runtime_root.join(project_id);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(REGISTRY_FILE),
+            serde_json::to_string(instance).unwrap(),
+        )
+        .unwrap();
+
+        File::open(&dir)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+
+        dir
+    }
+
+    fn dead_address() -> String {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_register() {
+        let dir = test_dir("register");
+        let runtime_root = dir.join("runtime");
+        let root_dir = dir.join("game");
+        fs::create_dir_all(&root_dir).unwrap();
+
+        let instance = Instance {
+            root_dir: root_dir.clone(),
+            ..instance("127.0.0.1:6666", 1234)
+        };
+
+        register_in(&runtime_root, &instance).unwrap();
+
+        let runtime_dir = nvim_server::runtime_dir_in(&runtime_root, &root_dir).unwrap();
+        let registered = read(&runtime_dir).unwrap();
+        assert_eq!(1234, registered.pid);
+        assert_eq!("127.0.0.1:6666", registered.address);
+        assert_eq!(root_dir, registered.root_dir);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune_and_list() {
+        let runtime_root = test_dir("prune");
+
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let live_address = server.local_addr().unwrap().to_string();
+
+        let live = runtime_dir(&runtime_root, "live", &instance(&live_address, 1));
+        let dead = runtime_dir(&runtime_root, "dead", &instance(&dead_address(), 2));
+
+        let launching = runtime_dir(&runtime_root, "launching", &instance(&dead_address(), 3));
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(launching.join("launch.lock"))
+            .unwrap();
+        lock.lock().unwrap();
+        File::open(&launching)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+
+        let young = runtime_root.join("young");
+        fs::create_dir_all(&young).unwrap();
+
+        prune_in(&runtime_root).unwrap();
+
+        assert!(live.exists());
+        assert!(!dead.exists());
+        assert!(launching.exists());
+        assert!(young.exists());
+
+        let instances = list_in(&runtime_root).unwrap();
+        assert_eq!(
+            vec![("live".to_string(), live_address)],
+            instances
+                .into_iter()
+                .map(|(id, instance)| (id, instance.address))
+                .collect::<Vec<_>>()
+        );
+
+        drop(lock);
+        prune_in(&runtime_root).unwrap();
+        assert!(!launching.exists());
+
+        fs::remove_dir_all(runtime_root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill() {
+        use std::process::Command;
+
+        let runtime_root = test_dir("kill");
+
+        let mut process = Command::new("sleep").arg("30").spawn().unwrap();
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        // the server is gone, so the pid can't be trusted anymore
+        let dir = runtime_dir(
+            &runtime_root,
+            "gone",
+            &instance(&dead_address(), process.id()),
+        );
+        assert!(kill_in(&runtime_root, "gone").is_err());
+        assert!(!dir.join(REGISTRY_FILE).exists());
+        assert!(process.try_wait().unwrap().is_none());
+
+        let dir = runtime_dir(
+            &runtime_root,
+            "running",
+            &instance(&server.local_addr().unwrap().to_string(), process.id()),
+        );
+        let killed = kill_in(&runtime_root, "running").unwrap();
+        assert_eq!(process.id(), killed.pid);
+        assert!(!process.wait().unwrap().success());
+        assert!(!dir.join(REGISTRY_FILE).exists());
+
+        assert!(kill_in(&runtime_root, "running").is_err());
+
+        fs::remove_dir_all(runtime_root).unwrap();
+    }
+}
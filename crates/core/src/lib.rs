@@ -1,16 +1,26 @@
 pub mod bridge;
 pub mod cache;
+pub mod config;
 pub mod defold_annotations;
+pub mod dependency_auth;
+pub mod dependency_override;
+pub mod dependency_store;
 pub mod editor;
 pub mod editor_config;
+pub mod engine;
 pub mod focus;
 pub mod game_project;
 pub mod github;
-pub mod mobdap;
-pub mod neovide;
+pub mod instances;
+pub mod multiplexer;
 pub mod nvim_server;
+pub mod offline;
 pub mod path;
 pub mod project;
+pub mod project_id;
 mod release_downloader;
 pub mod script_api;
+#[cfg(test)]
+mod test_utils;
+pub mod tools;
 pub mod utils;
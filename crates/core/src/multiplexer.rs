@@ -0,0 +1,498 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+use which::which;
+
+use crate::nvim_server;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Multiplexer {
+    #[serde(rename = "tmux")]
+    Tmux,
+
+    #[serde(rename = "zellij")]
+    Zellij,
+
+    #[serde(rename = "wezterm")]
+    WezTerm,
+}
+
+/// A pane (or tab for zellij) we've started Neovim in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pane {
+    pub multiplexer: Multiplexer,
+    pub session: Option<String>,
+    pub id: String,
+}
+
+impl Multiplexer {
+    pub(crate) fn executable_name(self) -> &'static str {
+        match self {
+            Multiplexer::Tmux => "tmux",
+            Multiplexer::Zellij => "zellij",
+            Multiplexer::WezTerm => "wezterm",
+        }
+    }
+
+    fn executable(self) -> Result<PathBuf> {
+        which(self.executable_name()).with_context(|| {
+            format!(
+                "Could not find {}, is it installed?",
+                self.executable_name()
+            )
+        })
+    }
+}
+
+fn pane_file(runtime_dir: &Path) -> PathBuf {
+    runtime_dir.join("pane.json")
+}
+
+fn run(mut cmd: Command) -> Result<String> {
+    tracing::debug!("Running {cmd:?}");
+
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = cmd.output()?;
+
+    if !status.success() {
+        bail!(
+            "{:?} failed with {status}: {}",
+            cmd.get_program(),
+            String::from_utf8_lossy(&stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8(stdout)?.trim().to_string())
+}
+
+/// Wraps the command with `env` so that the environment reaches the process started by the
+/// multiplexer server rather than just the client we're calling
+fn with_env(command: &[String], env: &HashMap<String, String>) -> Vec<String> {
+    if env.is_empty() || cfg!(target_os = "windows") {
+        return command.to_vec();
+    }
+
+    let mut args = vec!["env".to_string()];
+    args.extend(env.iter().map(|(k, v)| format!("{k}={v}")));
+    args.extend_from_slice(command);
+    args
+}
+
+fn kdl_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn zellij_session(zellij: &Path) -> Result<String> {
+    if let Ok(session) = std::env::var("ZELLIJ_SESSION_NAME") {
+        return Ok(session);
+    }
+
+    let mut cmd = Command::new(zellij);
+    cmd.args(["list-sessions", "--short", "--no-formatting"]);
+
+    run(cmd)?
+        .lines()
+        .map(str::trim)
+        .find(|s| !s.is_empty())
+        .map(ToString::to_string)
+        .context("Could not find a running zellij session")
+}
+
+fn zellij_action(zellij: &Path, session: &str) -> Command {
+    let mut cmd = Command::new(zellij);
+    cmd.arg("--session").arg(session).arg("action");
+    cmd
+}
+
+/// Opens `command` in a new pane of the running multiplexer and remembers it for the project
+pub fn spawn(
+    multiplexer: Multiplexer,
+    root_dir: &Path,
+    name: &str,
+    command: &[String],
+    env: &HashMap<String, String>,
+) -> Result<Pane> {
+    spawn_with(
+        &multiplexer.executable()?,
+        &nvim_server::runtime_dir(root_dir)?,
+        multiplexer,
+        root_dir,
+        name,
+        command,
+        env,
+    )
+}
+
+fn spawn_with(
+    executable: &Path,
+    runtime_dir: &Path,
+    multiplexer: Multiplexer,
+    root_dir: &Path,
+    name: &str,
+    command: &[String],
+    env: &HashMap<String, String>,
+) -> Result<Pane> {
+    let command = with_env(command, env);
+
+    let pane = match multiplexer {
+        Multiplexer::Tmux => {
+            let mut cmd = Command::new(executable);
+            cmd.args(["new-window", "-P", "-F", "#{pane_id}", "-n", name, "-c"])
+                .arg(root_dir)
+                .arg("--")
+                .args(&command);
+
+            Pane {
+                multiplexer,
+                session: None,
+                id: run(cmd)?,
+            }
+        }
+        Multiplexer::Zellij => {
+            let session = zellij_session(executable)?;
+
+            let (program, args) = command.split_first().context("empty command")?;
+
+            let layout = format!(
+                "layout {{\n    pane command={} cwd={} close_on_exit=true {{\n        args {}\n    }}\n}}\n",
+                kdl_string(program),
+                kdl_string(
+                    root_dir
+                        .to_str()
+                        .context("could not convert path to string")?
+                ),
+                args.iter()
+                    .map(|arg| kdl_string(arg))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+
+            let layout_file = runtime_dir.join("zellij_layout.kdl");
+            fs::write(&layout_file, layout)?;
+
+            let mut cmd = zellij_action(executable, &session);
+            cmd.args(["new-tab", "--name", name, "--layout"])
+                .arg(&layout_file);
+            run(cmd)?;
+
+            Pane {
+                multiplexer,
+                session: Some(session),
+                id: name.to_string(),
+            }
+        }
+        Multiplexer::WezTerm => {
+            let mut cmd = Command::new(executable);
+            cmd.args(["cli", "spawn", "--cwd"])
+                .arg(root_dir)
+                .arg("--")
+                .args(&command);
+
+            Pane {
+                multiplexer,
+                session: None,
+                id: run(cmd)?,
+            }
+        }
+    };
+
+    tracing::debug!("Started Neovim in {pane:?}");
+
+    fs::write(pane_file(runtime_dir), serde_json::to_string(&pane)?)?;
+
+    Ok(pane)
+}
+
+impl Pane {
+    #[must_use]
+    pub fn is_alive(&self) -> bool {
+        self.multiplexer
+            .executable()
+            .is_ok_and(|executable| self.is_alive_with(&executable))
+    }
+
+    fn is_alive_with(&self, executable: &Path) -> bool {
+        let check = || -> Result<bool> {
+            match self.multiplexer {
+                Multiplexer::Tmux => {
+                    let mut cmd = Command::new(executable);
+                    cmd.args(["display-message", "-p", "-t", &self.id, "#{pane_id}"]);
+                    Ok(run(cmd)? == self.id)
+                }
+                Multiplexer::Zellij => {
+                    let mut cmd =
+                        zellij_action(executable, self.session.as_deref().unwrap_or_default());
+                    cmd.arg("query-tab-names");
+                    Ok(run(cmd)?.lines().any(|tab| tab.trim() == self.id))
+                }
+                Multiplexer::WezTerm => {
+                    let mut cmd = Command::new(executable);
+                    cmd.args(["cli", "list", "--format", "json"]);
+
+                    let panes: Vec<Value> = serde_json::from_str(&run(cmd)?)?;
+
+                    Ok(panes
+                        .iter()
+                        .filter_map(|pane| pane["pane_id"].as_u64())
+                        .any(|pane_id| pane_id.to_string() == self.id))
+                }
+            }
+        };
+
+        check().unwrap_or_else(|err| {
+            tracing::debug!("Could not check if {self:?} is alive: {err:?}");
+            false
+        })
+    }
+
+    pub fn focus(&self) -> Result<()> {
+        tracing::info!("Focusing {self:?}");
+
+        let executable = self.multiplexer.executable()?;
+
+        match self.multiplexer {
+            Multiplexer::Tmux => {
+                let mut cmd = Command::new(&executable);
+                cmd.args(["select-window", "-t", &self.id]);
+                run(cmd)?;
+
+                let mut cmd = Command::new(&executable);
+                cmd.args(["select-pane", "-t", &self.id]);
+                run(cmd)?;
+
+                // only works if there is an attached client, so don't care if it fails
+                let mut cmd = Command::new(&executable);
+                cmd.args(["switch-client", "-t", &self.id]);
+                if let Err(err) = run(cmd) {
+                    tracing::debug!("Could not switch tmux client: {err:?}");
+                }
+            }
+            Multiplexer::Zellij => {
+                let mut cmd =
+                    zellij_action(&executable, self.session.as_deref().unwrap_or_default());
+                cmd.args(["go-to-tab-name", &self.id]);
+                run(cmd)?;
+            }
+            Multiplexer::WezTerm => {
+                let mut cmd = Command::new(&executable);
+                cmd.args(["cli", "activate-pane", "--pane-id", &self.id]);
+                run(cmd)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the pane Neovim was started in for this project, if it is still around
+#[must_use]
+pub fn find_pane(root_dir: &Path) -> Option<Pane> {
+    find_pane_with(&nvim_server::runtime_dir(root_dir).ok()?, Pane::is_alive)
+}
+
+fn find_pane_with(runtime_dir: &Path, is_alive: impl Fn(&Pane) -> bool) -> Option<Pane> {
+    let file = pane_file(runtime_dir);
+
+    if !file.exists() {
+        return None;
+    }
+
+    let pane: Pane = serde_json::from_str(&fs::read_to_string(&file).ok()?).ok()?;
+
+    if is_alive(&pane) {
+        return Some(pane);
+    }
+
+    tracing::debug!("{pane:?} is gone, forgetting about it");
+    fs::remove_file(file).ok();
+
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::multiplexer::{Multiplexer, Pane, find_pane_with, pane_file, spawn_with};
+    use pretty_assertions::assert_eq;
+    use std::{
+        collections::HashMap,
+        env, fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+    };
+
+    /// Creates a fake multiplexer that records its arguments and prints `name.out`
+    fn fake_binary(dir: &Path, name: &str, out: &str) -> PathBuf {
+        let path = dir.join(name);
+
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\nfor arg in \"$@\"; do printf '[%s]' \"$arg\"; done >> '{}.log'\necho >> '{}.log'\ncat \"$0.out\"\n",
+                path.display(),
+                path.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(dir.join(format!("{name}.out")), out).unwrap();
+
+        path
+    }
+
+    fn log(dir: &Path, name: &str) -> String {
+        fs::read_to_string(dir.join(format!("{name}.log"))).unwrap()
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("multiplexer-{name}-{}", std::process::id()));
+        fs::create_dir_all(dir.join("runtime")).unwrap();
+        dunce::canonicalize(dir).unwrap()
+    }
+
+    fn command() -> Vec<String> {
+        vec![
+            "nvim".to_string(),
+            "--listen".to_string(),
+            "my file".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_spawn_tmux() {
+        let dir = test_dir("tmux");
+        let tmux = fake_binary(&dir, "tmux", "%5\n");
+
+        let pane = spawn_with(
+            &tmux,
+            &dir.join("runtime"),
+            Multiplexer::Tmux,
+            &dir,
+            "game",
+            &command(),
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!("%5", pane.id);
+        assert_eq!(
+            format!(
+                "[new-window][-P][-F][#{{pane_id}}][-n][game][-c][{}][--][nvim][--listen][my file]\n",
+                dir.display()
+            ),
+            log(&dir, "tmux")
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_spawn_zellij() {
+        let dir = test_dir("zellij");
+        let zellij = fake_binary(&dir, "zellij", "main\n");
+
+        let pane = spawn_with(
+            &zellij,
+            &dir.join("runtime"),
+            Multiplexer::Zellij,
+            &dir,
+            "game",
+            &command(),
+            &HashMap::from([("NVIM_APPNAME".to_string(), "my \"nvim\"".to_string())]),
+        )
+        .unwrap();
+
+        let session = pane.session.clone().unwrap();
+        assert_eq!("game", pane.id);
+
+        let layout_file = dir.join("runtime").join("zellij_layout.kdl");
+        assert!(log(&dir, "zellij").ends_with(&format!(
+            "[--session][{session}][action][new-tab][--name][game][--layout][{}]\n",
+            layout_file.display()
+        )));
+        assert_eq!(
+            format!(
+                "layout {{\n    pane command=\"env\" cwd=\"{}\" close_on_exit=true {{\n        args \"NVIM_APPNAME=my \\\"nvim\\\"\" \"nvim\" \"--listen\" \"my file\"\n    }}\n}}\n",
+                dir.display()
+            ),
+            fs::read_to_string(layout_file).unwrap()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_spawn_wezterm() {
+        let dir = test_dir("wezterm");
+        let wezterm = fake_binary(&dir, "wezterm", "12\n");
+
+        let pane = spawn_with(
+            &wezterm,
+            &dir.join("runtime"),
+            Multiplexer::WezTerm,
+            &dir,
+            "game",
+            &command(),
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!("12", pane.id);
+        assert_eq!(
+            format!(
+                "[cli][spawn][--cwd][{}][--][nvim][--listen][my file]\n",
+                dir.display()
+            ),
+            log(&dir, "wezterm")
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_pane() {
+        let dir = test_dir("find-pane");
+        let wezterm = fake_binary(&dir, "wezterm", "12\n");
+
+        spawn_with(
+            &wezterm,
+            &dir.join("runtime"),
+            Multiplexer::WezTerm,
+            &dir,
+            "game",
+            &command(),
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        fs::write(
+            dir.join("wezterm.out"),
+            r#"[{"pane_id": 3}, {"pane_id": 12}]"#,
+        )
+        .unwrap();
+
+        let is_alive = |pane: &Pane| pane.is_alive_with(&wezterm);
+
+        let pane = find_pane_with(&dir.join("runtime"), is_alive).unwrap();
+        assert_eq!("12", pane.id);
+        assert!(log(&dir, "wezterm").ends_with("[cli][list][--format][json]\n"));
+
+        // the pane was closed, so it is forgotten
+        fs::write(dir.join("wezterm.out"), r#"[{"pane_id": 3}]"#).unwrap();
+        assert!(find_pane_with(&dir.join("runtime"), is_alive).is_none());
+        assert!(!pane_file(&dir.join("runtime")).exists());
+        assert!(find_pane_with(&dir.join("runtime"), is_alive).is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,10 @@
-use crate::{path, utils::project_id};
+use crate::{path, project_id::ProjectId};
 use anyhow::{Context, Result, bail};
 use std::{
-    fs,
-    net::TcpListener,
+    fs::{self, File, OpenOptions},
+    net::{SocketAddr, TcpListener, TcpStream},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -31,19 +32,56 @@ pub fn default_socket_type() -> SocketType {
     }
 }
 
+pub fn runtime_root() -> Result<PathBuf> {
+    Ok(path::cache_dir()?.join("runtime"))
+}
+
 pub fn runtime_dir(root_dir: &Path) -> Result<PathBuf> {
-    let root_dir = root_dir
-        .to_str()
-        .context("could not convert root dir to string")?;
+    runtime_dir_in(&runtime_root()?, root_dir)
+}
 
-    let dir = path::cache_dir()?
-        .join("runtime")
-        .join(project_id(root_dir)?);
+pub fn runtime_dir_in(runtime_root: &Path, root_dir: &Path) -> Result<PathBuf> {
+    let dir = runtime_root.join(ProjectId::from_root(root_dir)?.as_str());
 
     fs::create_dir_all(&dir)?;
     Ok(dir)
 }
 
+/// Blocks until no other bridge is launching Neovim for this project, the lock is held
+/// until the returned file is dropped
+pub fn lock_launch(root_dir: &Path) -> Result<File> {
+    lock_launch_in(&runtime_root()?, root_dir)
+}
+
+pub fn lock_launch_in(runtime_root: &Path, root_dir: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(runtime_dir_in(runtime_root, root_dir)?.join("launch.lock"))?;
+
+    tracing::debug!("Waiting for launch lock...");
+    file.lock()?;
+    tracing::debug!("Acquired launch lock");
+
+    Ok(file)
+}
+
+/// Checks whether something accepts connections at a server address, which is either
+/// `host:port` or the path to a socket
+#[must_use]
+pub fn is_server_reachable(addr: &str) -> bool {
+    if let Ok(socket_addr) = addr.parse::<SocketAddr>() {
+        return TcpStream::connect_timeout(&socket_addr, Duration::from_millis(500)).is_ok();
+    }
+
+    #[cfg(unix)]
+    return std::os::unix::net::UnixStream::connect(addr).is_ok();
+
+    #[cfg(windows)]
+    return OpenOptions::new().read(true).write(true).open(addr).is_ok();
+}
+
 pub fn fsock_path(root_dir: &Path) -> Result<PathBuf> {
     Ok(runtime_dir(root_dir)?.join("neovim.sock"))
 }
@@ -52,7 +90,7 @@ pub fn netsock_port_file(root_dir: &Path) -> Result<PathBuf> {
     Ok(runtime_dir(root_dir)?.join("port"))
 }
 
-#[must_use]
+#[must_use = "the port is not reserved, use it right away"]
 pub fn find_free_port() -> Result<u16> {
     let listener = TcpListener::bind("127.0.0.1:0")?;
     let port = listener.local_addr()?.port();
@@ -0,0 +1,109 @@
+use std::{env, path::Path};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    config, defold_annotations, dependency_auth, game_project::GameProject, project, tools,
+};
+
+/// Overrides `offline` in the global config, e.g. `DEFOLD_NVIM_OFFLINE=1`
+pub const ENV_VAR: &str = "DEFOLD_NVIM_OFFLINE";
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "" => None,
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => Some(true),
+    }
+}
+
+/// Whether offline mode is on, in which nothing is downloaded and only what's installed or cached is used
+#[must_use]
+pub fn is_enabled() -> bool {
+    if let Some(enabled) = env::var(ENV_VAR).ok().as_deref().and_then(parse_flag) {
+        return enabled;
+    }
+
+    match config::load(None) {
+        Ok(config) => config.offline,
+        Err(err) => {
+            tracing::error!("Could not load config, assuming online: {err:?}");
+            false
+        }
+    }
+}
+
+/// Fails if offline mode is on, called before anything touches the network
+pub fn ensure_online(url: &str) -> Result<()> {
+    if is_enabled() {
+        bail!("Offline mode is enabled, not requesting {url}");
+    }
+
+    Ok(())
+}
+
+fn tool_feature(name: &str) -> Option<&'static str> {
+    match name {
+        "mobdap" => Some("debugging"),
+        "neovide" => Some("the Neovide launcher"),
+        "defold-nvim-bridge" => Some("opening files from the Defold editor"),
+        _ => None,
+    }
+}
+
+/// What doesn't work because of offline mode, empty if it is off
+pub fn degraded_features(game_root: Option<&Path>) -> Result<Vec<String>> {
+    if !is_enabled() {
+        return Ok(Vec::new());
+    }
+
+    let mut degraded = vec!["Tools and annotations are not checked for updates".to_string()];
+
+    for tool in tools::list()? {
+        if tool.size.is_some() {
+            continue;
+        }
+
+        degraded.push(match tool_feature(&tool.name) {
+            Some(feature) => format!("{} is not installed, {feature} is unavailable", tool.name),
+            None => format!("{} is not installed", tool.name),
+        });
+    }
+
+    if !defold_annotations::dir()?.exists() {
+        degraded.push(
+            "Defold annotations are not installed, there is no completion for the Defold API"
+                .to_string(),
+        );
+    }
+
+    if let Some(game_root) = game_root {
+        let game_project = GameProject::load_from_path(&game_root.join("game.project"))?;
+
+        for url in &game_project.dependencies {
+            if !project::is_dependency_installed(game_root, url)? {
+                degraded.push(format!(
+                    "Dependency {} is not installed",
+                    dependency_auth::redact(url)
+                ));
+            }
+        }
+    }
+
+    Ok(degraded)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::offline::parse_flag;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_flag() {
+        assert_eq!(None, parse_flag(""));
+        assert_eq!(Some(true), parse_flag("1"));
+        assert_eq!(Some(true), parse_flag("TRUE"));
+        assert_eq!(Some(false), parse_flag("0"));
+        assert_eq!(Some(false), parse_flag(" off "));
+    }
+}
@@ -1,19 +1,31 @@
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
     io::Write,
     path::{Path, PathBuf},
 };
 
 use crate::{
-    defold_annotations,
+    config, defold_annotations,
+    dependency_auth::{self, HostAuth},
+    dependency_override::DependencyOverride,
+    dependency_store::{self, Manifest},
     game_project::GameProject,
-    path, script_api,
-    utils::{self, sha3},
+    offline, path,
+    project_id::ProjectId,
+    script_api,
+    utils::{self, Validators, sha3},
 };
 use anyhow::{Context, Result, bail};
 use walkdir::WalkDir;
 use zip::ZipArchive;
 
+/// Include dirs of overridden dependencies, by ident
+const OVERRIDES_DIR: &str = "local";
+
+/// Checkouts of dependencies overridden with a git repository, by ident
+const CHECKOUTS_DIR: &str = "git";
+
 fn ident(string: &str) -> Result<String> {
     let hash = sha3(string);
     let ident_str = hash.get(..8).context("could not create hash")?;
@@ -29,7 +41,7 @@ pub fn deps_root() -> Result<PathBuf> {
 fn deps_dir(game_root: &Path) -> Result<PathBuf> {
     let dir = deps_root()?
         .join("project")
-        .join(ident(&game_root.display().to_string())?);
+        .join(ProjectId::from_root(game_root)?.as_str());
     fs::create_dir_all(&dir)?;
     Ok(dir)
 }
@@ -43,11 +55,28 @@ pub fn list_dependency_dirs(game_root: &Path) -> Result<Vec<PathBuf>> {
         deps.push(annotations);
     }
 
-    if let Ok(dep_dirs) = deps_dir(game_root) {
-        for entry in fs::read_dir(dep_dirs)? {
+    if let Ok(proj_deps_dir) = deps_dir(game_root) {
+        for hash in Manifest::load(&proj_deps_dir)?.dependencies.values() {
+            let entry = dependency_store::entry_path(hash)?;
+
+            if entry.exists() {
+                deps.push(entry);
+            }
+        }
+
+        let overrides_dir = proj_deps_dir.join(OVERRIDES_DIR);
+
+        if overrides_dir.exists() {
+            for entry in fs::read_dir(overrides_dir)? {
+                deps.push(entry?.path());
+            }
+        }
+
+        // copies made before dependencies were shared between projects
+        for entry in fs::read_dir(proj_deps_dir)? {
             let path = entry?.path();
 
-            if !path.is_dir() {
+            if !path.is_dir() || path.ends_with(OVERRIDES_DIR) || path.ends_with(CHECKOUTS_DIR) {
                 continue;
             }
 
@@ -68,27 +97,149 @@ pub fn install_dependencies(game_root: &Path, force_redownload: bool) -> Result<
         );
     }
 
-    defold_annotations::install()?;
+    ProjectId::migrate(game_root)?;
+
+    defold_annotations::install(Some(game_root))?;
 
     let proj_deps_dir = deps_dir(game_root)?;
+    let offline = offline::is_enabled();
 
-    if force_redownload {
-        fs::remove_dir_all(&proj_deps_dir)?;
+    if force_redownload && offline {
+        tracing::warn!("Offline mode is enabled, keeping the installed dependencies");
     }
 
+    let force_redownload = force_redownload && !offline;
+
     let game_project = GameProject::load_from_path(&game_project_path)?;
 
+    let (auth, overrides) = match config::load(Some(game_root)) {
+        Ok(config) => (config.dependency_auth, config.dependency_overrides),
+        Err(err) => {
+            tracing::error!(
+                "Could not load config, using no dependency credentials or overrides: {err:?}"
+            );
+            (BTreeMap::new(), BTreeMap::new())
+        }
+    };
+
+    let mut manifest = Manifest::load(&proj_deps_dir)?;
+    let mut dependencies = BTreeMap::new();
+    let mut validators = BTreeMap::new();
+
     for dep_url in &game_project.dependencies {
-        if let Err(err) = install_dependency(dep_url, &proj_deps_dir) {
-            tracing::error!("Could not download dependency {dep_url} because: {err:?}");
+        let name = dependency_auth::redact(dep_url);
+
+        if let Some(dep_override) = overrides.get(dep_url) {
+            let ident = ident(dep_url)?;
+
+            let installed = dep_override
+                .source_root(
+                    game_root,
+                    &proj_deps_dir.join(CHECKOUTS_DIR).join(&ident),
+                    &auth,
+                )
+                .and_then(|source| {
+                    install_local_dependency(
+                        &name,
+                        &source,
+                        &proj_deps_dir.join(OVERRIDES_DIR).join(&ident),
+                    )
+                });
+
+            if let Err(err) = installed {
+                tracing::error!("Could not use override for dependency {name}: {err:?}");
+            }
+
+            continue;
+        }
+
+        let installed = match manifest.dependencies.get(dep_url) {
+            Some(hash) if !force_redownload => Some((
+                hash.clone(),
+                manifest
+                    .validators
+                    .get(dep_url)
+                    .cloned()
+                    .unwrap_or_default(),
+            )),
+            _ if force_redownload => None,
+            _ => dependency_store::find(dep_url)?,
+        }
+        .filter(|(hash, _)| dependency_store::entry_path(hash).is_ok_and(|entry| entry.exists()));
+
+        if let Some((hash, known)) = installed {
+            // without validators there is no cheap way to tell if the archive changed
+            let installed = if offline || known.is_empty() {
+                tracing::debug!("Dependency {name} is already installed, skipping...");
+                None
+            } else {
+                match install_dependency(dep_url, &auth, &known) {
+                    Ok(changed) => changed,
+                    Err(err) => {
+                        tracing::error!("Could not check dependency {name} for changes: {err:?}");
+                        None
+                    }
+                }
+            };
+
+            let (hash, known) = match installed {
+                Some((new_hash, new_validators)) => {
+                    if new_hash != hash {
+                        tracing::info!("Updated dependency {name}, it changed");
+                    }
+                    (new_hash, new_validators)
+                }
+                None => (hash, known),
+            };
+
+            dependencies.insert(dep_url.clone(), hash);
+            validators.insert(dep_url.clone(), known);
+            continue;
+        }
+
+        if offline {
+            if !proj_deps_dir.join(ident(dep_url)?).exists() {
+                tracing::warn!("Dependency {name} is not installed and offline mode is enabled");
+            }
+
+            continue;
+        }
+
+        match install_dependency(dep_url, &auth, &Validators::default()) {
+            Ok(Some((hash, known))) => {
+                dependencies.insert(dep_url.clone(), hash);
+                validators.insert(dep_url.clone(), known);
+            }
+            Ok(None) => {
+                tracing::error!("Could not download dependency {name}, got no content");
+            }
+            Err(err) => {
+                tracing::error!("Could not download dependency {name} because: {err:?}");
+            }
         }
     }
 
-    // delete unused dirs
+    validators.retain(|_, known| !known.is_empty());
+
+    manifest.root = game_root.to_path_buf();
+    manifest.dependencies = dependencies;
+    manifest.validators = validators;
+    manifest.save(&proj_deps_dir)?;
+
+    remove_stale_overrides(&proj_deps_dir.join(OVERRIDES_DIR), overrides.keys())?;
+    remove_stale_overrides(
+        &proj_deps_dir.join(CHECKOUTS_DIR),
+        overrides
+            .iter()
+            .filter(|(_, dep_override)| matches!(dep_override, DependencyOverride::Git { .. }))
+            .map(|(url, _)| url),
+    )?;
+
+    // delete copies made before dependencies were shared, unless they are still needed offline
     let dep_dirs = fs::read_dir(&proj_deps_dir)?
         .filter_map(Result::ok)
         .map(|f| f.path())
-        .filter(|f| f.is_dir());
+        .filter(|f| f.is_dir() && !f.ends_with(OVERRIDES_DIR) && !f.ends_with(CHECKOUTS_DIR));
 
     'outer: for dir in dep_dirs {
         let Some(name) = dir
@@ -100,36 +251,159 @@ pub fn install_dependencies(game_root: &Path, force_redownload: bool) -> Result<
         };
 
         for dep in &game_project.dependencies {
-            let dep_ident = ident(dep)?;
-
-            if name == dep_ident {
+            if name == ident(dep)?
+                && !manifest.dependencies.contains_key(dep)
+                && !overrides.contains_key(dep)
+            {
                 continue 'outer;
             }
         }
 
-        tracing::debug!("Removing unused dependency dir {}", dir.display());
+        tracing::debug!("Removing dependency dir {}", dir.display());
         fs::remove_dir_all(dir)?;
     }
 
+    if let Err(err) = dependency_store::collect_garbage(false) {
+        tracing::error!("Could not remove unused dependencies: {err:?}");
+    }
+
     utils::delete_empty_dirs_from(game_root)?;
 
     Ok(())
 }
 
-fn install_dependency(url: &str, project_deps_dir: &Path) -> Result<()> {
-    let target_dir = project_deps_dir.join(ident(url)?);
+pub fn is_dependency_installed(game_root: &Path, url: &str) -> Result<bool> {
+    let proj_deps_dir = deps_dir(game_root)?;
 
-    if target_dir.exists() {
-        tracing::debug!("Dependency {url} does already exist, skipping...");
+    if let Some(hash) = Manifest::load(&proj_deps_dir)?.dependencies.get(url)
+        && dependency_store::entry_path(hash)?.exists()
+    {
+        return Ok(true);
+    }
+
+    let ident = ident(url)?;
+
+    Ok(proj_deps_dir.join(OVERRIDES_DIR).join(&ident).exists()
+        || proj_deps_dir.join(ident).exists())
+}
+
+/// Removes the dirs of dependencies in `dir` that aren't overridden anymore
+fn remove_stale_overrides<'a>(dir: &Path, urls: impl Iterator<Item = &'a String>) -> Result<()> {
+    if !dir.exists() {
         return Ok(());
     }
 
-    tracing::info!("Downloading {url} to {}...", target_dir.display());
+    let keep = urls.map(|url| ident(url)).collect::<Result<Vec<_>>>()?;
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| keep.iter().any(|ident| ident == name))
+        {
+            continue;
+        }
+
+        tracing::debug!("Removing dependency override {}", path.display());
+        fs::remove_dir_all(path)?;
+    }
+
+    Ok(())
+}
+
+/// Makes the include dirs of a local checkout of a dependency available. They are linked where
+/// possible, so changes show up without installing the dependencies again
+fn install_local_dependency(name: &str, source_root: &Path, target: &Path) -> Result<()> {
+    let game_project = GameProject::load_from_path(&source_root.join("game.project"))?;
+
+    let Some(library) = &game_project.library else {
+        bail!("Dependency {name} does not contain key library.include_dirs");
+    };
+
+    // never follows the links, the checkout itself stays untouched
+    if target.exists() {
+        fs::remove_dir_all(target)?;
+    }
+
+    fs::create_dir_all(target)?;
+
+    for include_dir in &library.include_dirs {
+        let include_dir_path = source_root.join(include_dir);
+        let include_dir_target = target.join(include_dir);
+
+        if !include_dir_path.exists() {
+            tracing::warn!(
+                "Dependency {name} has specified include dir {include_dir} but it doesn't actually exist at {}. Skipping...",
+                include_dir_path.display()
+            );
+            continue;
+        }
+
+        // script api files are compiled next to themselves, which must not happen in the checkout
+        if find_files_with_ext(&include_dir_path, "script_api").is_empty()
+            && link_dir(&include_dir_path, &include_dir_target).is_ok()
+        {
+            continue;
+        }
+
+        copy_files(&include_dir_path, &include_dir_target, "lua")?;
+        copy_files(&include_dir_path, &include_dir_target, "script_api")?;
+        compile_script_api_files(&include_dir_target)?;
+    }
 
-    let downloaded_file = utils::download(url)?;
+    tracing::debug!("Using {} for dependency {name}", source_root.display());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link_dir(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    std::os::unix::fs::symlink(from, to)?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn link_dir(_from: &Path, _to: &Path) -> Result<()> {
+    bail!("Linking dirs is only supported on unix")
+}
+
+/// Downloads the dependency into the shared store, returns the hash of its archive and its validators.
+/// Returns nothing if it didn't change since `validators` were received
+fn install_dependency(
+    url: &str,
+    auth: &BTreeMap<String, HostAuth>,
+    validators: &Validators,
+) -> Result<Option<(String, Validators)>> {
+    let name = dependency_auth::redact(url);
+    let source = dependency_auth::resolve(url, auth)?;
+
+    tracing::debug!("Checking {name} for changes...");
+
+    let Some((downloaded_file, validators)) =
+        utils::download_if_changed(&source.url, source.credentials.as_ref(), validators)?
+    else {
+        return Ok(None);
+    };
 
     tracing::debug!("Downloaded to {}", downloaded_file.display());
 
+    let hash = utils::sha256_file(&downloaded_file)?;
+
+    if dependency_store::entry_path(&hash)?.exists() {
+        tracing::debug!("Dependency {name} is in the store already as {hash}");
+        utils::clear_download(&source.url)?;
+        return Ok(Some((hash, validators)));
+    }
+
+    let staging = dependency_store::staging_path(&hash)?;
+
     let parent_dir = downloaded_file
         .parent()
         .context("could not get parent dir of downloaded file")?;
@@ -148,22 +422,22 @@ fn install_dependency(url: &str, project_deps_dir: &Path) -> Result<()> {
     let game_project = GameProject::load_from_path(&game_project_file)?;
 
     let Some(library) = &game_project.library else {
-        utils::clear_download(url)?;
-        bail!("Dependency {url} does not contain key library.include_dirs");
+        utils::clear_download(&source.url)?;
+        bail!("Dependency {name} does not contain key library.include_dirs");
     };
 
     if library.include_dirs.is_empty() {
-        utils::clear_download(url)?;
-        bail!("Dependency {url} does not contain any library.include_dirs");
+        utils::clear_download(&source.url)?;
+        bail!("Dependency {name} does not contain any library.include_dirs");
     }
 
     for include_dir in &library.include_dirs {
         let include_dir_path = game_root.join(include_dir);
-        let include_dir_target = target_dir.join(include_dir);
+        let include_dir_target = staging.join(include_dir);
 
         if !include_dir_path.exists() {
             tracing::warn!(
-                "Dependency {url} has specified include dir {include_dir} but it doesn't actually exist at {}. Skipping...",
+                "Dependency {name} has specified include dir {include_dir} but it doesn't actually exist at {}. Skipping...",
                 include_dir_path.display()
             );
             continue;
@@ -173,9 +447,13 @@ fn install_dependency(url: &str, project_deps_dir: &Path) -> Result<()> {
         copy_files(&include_dir_path, &include_dir_target, "lua")?;
     }
 
-    utils::clear_download(url)?;
+    utils::clear_download(&source.url)?;
 
-    Ok(())
+    let entry = dependency_store::commit(&staging, &hash)?;
+
+    tracing::info!("Installed {name} to {}", entry.display());
+
+    Ok(Some((hash, validators)))
 }
 
 fn find_game_project(root_dir: &Path) -> Result<PathBuf> {
@@ -246,3 +524,65 @@ fn copy_files(from_dir: &Path, to_dir: &Path, ext: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::project::install_local_dependency;
+    use std::{env, fs};
+
+    const ASTAR_SCRIPT_API: &str = include_str!("../fixtures/astar.script_api");
+
+    #[test]
+    fn test_install_local_dependency() {
+        let dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("local-dependency-{}", std::process::id()));
+        let checkout = dir.join("checkout");
+        let target = dir.join("target");
+
+        fs::create_dir_all(checkout.join("lib")).unwrap();
+        fs::create_dir_all(checkout.join("api")).unwrap();
+        fs::write(
+            checkout.join("game.project"),
+            "[project]\ntitle = Library\n\n[library]\ninclude_dirs = lib,api,missing\n",
+        )
+        .unwrap();
+        fs::write(checkout.join("lib").join("main.lua"), "return {}").unwrap();
+        fs::write(checkout.join("api").join("helper.lua"), "return {}").unwrap();
+        fs::write(
+            checkout.join("api").join("astar.script_api"),
+            ASTAR_SCRIPT_API,
+        )
+        .unwrap();
+
+        // installing twice replaces the previous install without touching the checkout
+        for _ in 0..2 {
+            install_local_dependency("lib", &checkout, &target).unwrap();
+        }
+
+        // plain lua dirs are linked, so edits in the checkout show up right away
+        #[cfg(unix)]
+        assert_eq!(
+            checkout.join("lib"),
+            fs::read_link(target.join("lib")).unwrap()
+        );
+        assert!(target.join("lib").join("main.lua").exists());
+        assert!(checkout.join("lib").join("main.lua").exists());
+
+        // script apis are compiled in a copy
+        let api = target.join("api");
+        assert!(!fs::symlink_metadata(&api).unwrap().is_symlink());
+        assert!(api.join("helper.lua").exists());
+        assert!(api.join("astar.script_api").exists());
+        assert!(
+            fs::read_to_string(api.join("astar.lua"))
+                .unwrap()
+                .contains("astar")
+        );
+        assert!(!checkout.join("api").join("astar.lua").exists());
+
+        assert!(!target.join("missing").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{config, path, utils::sha3};
+
+/// Stable identity of a project, used to name its sockets, dependency dirs and window class
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProjectId(String);
+
+impl ProjectId {
+    /// Derives the id from the canonicalized root, so symlinks and different spellings of the
+    /// same path end up with the same id. Can be overridden with `project.id` in the project
+    /// config file
+    pub fn from_root(root_dir: &Path) -> Result<Self> {
+        Self::from_canonical(&canonicalize(root_dir)?)
+    }
+
+    fn from_canonical(canonical: &Path) -> Result<Self> {
+        let id = match config::project_id_override(canonical)? {
+            Some(id) => {
+                if id.is_empty()
+                    || !id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                {
+                    bail!(
+                        "Invalid project id '{id}', only letters, digits, '-' and '_' are allowed"
+                    );
+                }
+
+                Self(id)
+            }
+            None => Self::hash(canonical)?,
+        };
+
+        Ok(id)
+    }
+
+    /// Moves data older versions created under a different id over to the current one, only
+    /// needs to run once when the project is set up
+    pub fn migrate(root_dir: &Path) -> Result<Self> {
+        let canonical = canonicalize(root_dir)?;
+        let id = Self::from_canonical(&canonical)?;
+
+        id.migrate_legacy(root_dir, &canonical);
+
+        Ok(id)
+    }
+
+    fn hash(path: &Path) -> Result<Self> {
+        let path = path.to_str().context("could not convert path to string")?;
+
+        Ok(Self(
+            sha3(path)
+                .get(0..8)
+                .context("could not create project id")?
+                .to_string(),
+        ))
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn classname(&self) -> String {
+        format!("com.defold.nvim.{self}")
+    }
+
+    /// Older versions hashed the root exactly as it was passed in, move whatever they created
+    /// over to the current id
+    fn migrate_legacy(&self, root_dir: &Path, canonical: &Path) {
+        let mut legacy_ids = Vec::new();
+
+        for path in [root_dir, canonical] {
+            if let Ok(id) = Self::hash(path)
+                && id != *self
+                && !legacy_ids.contains(&id)
+            {
+                legacy_ids.push(id);
+            }
+        }
+
+        for legacy in legacy_ids {
+            if let Err(err) = self.migrate_from(&legacy) {
+                tracing::error!("Could not migrate project data of {legacy} to {self}: {err:?}");
+            }
+        }
+    }
+
+    fn migrate_from(&self, legacy: &ProjectId) -> Result<()> {
+        for (dir, suffix) in per_project_locations()? {
+            let from = dir.join(format!("{legacy}{suffix}"));
+            let to = dir.join(format!("{self}{suffix}"));
+
+            if !from.exists() || to.exists() {
+                continue;
+            }
+
+            tracing::info!("Migrating {} to {}", from.display(), to.display());
+            fs::rename(from, to)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ProjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Places that store data named after the project id, along with the file suffix
+fn per_project_locations() -> Result<Vec<(PathBuf, &'static str)>> {
+    let data_dir = path::data_dir()?;
+
+    Ok(vec![
+        (path::cache_dir()?.join("runtime"), ""),
+        (data_dir.join("runners"), ""),
+        (data_dir.join("deps").join("project"), ""),
+        (data_dir.join("editor_prefs"), ".json"),
+    ])
+}
+
+fn canonicalize(root_dir: &Path) -> Result<PathBuf> {
+    // dunce avoids the \\?\ prefix on windows, which would change the id depending on how
+    // the path was spelled
+    match dunce::canonicalize(root_dir) {
+        Ok(path) => Ok(path),
+        Err(err) => {
+            tracing::debug!("Could not canonicalize {}: {err}", root_dir.display());
+
+            // still normalize what we can for roots that don't exist (anymore)
+            Ok(std::path::absolute(root_dir)?.components().collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project_id::ProjectId;
+    use pretty_assertions::assert_eq;
+    use std::{env, fs};
+
+    #[test]
+    fn test_same_id_for_different_spellings() {
+        let dir = env::temp_dir().join(format!("defold-nvim-project-id-{}", std::process::id()));
+        let root = dir.join("game");
+        fs::create_dir_all(&root).unwrap();
+
+        let id = ProjectId::from_root(&root).unwrap();
+
+        assert_eq!(
+            id,
+            ProjectId::from_root(&dir.join("game").join("")).unwrap()
+        );
+        assert_eq!(
+            id,
+            ProjectId::from_root(&root.join("..").join("game")).unwrap()
+        );
+
+        #[cfg(unix)]
+        {
+            let link = dir.join("link");
+            std::os::unix::fs::symlink(&root, &link).unwrap();
+            assert_eq!(id, ProjectId::from_root(&link).unwrap());
+        }
+
+        fs::write(
+            root.join(".defold.nvim.toml"),
+            "[project]\nid = \"my-game\"\n",
+        )
+        .unwrap();
+        assert_eq!("my-game", ProjectId::from_root(&root).unwrap().as_str());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
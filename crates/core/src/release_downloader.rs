@@ -1,38 +1,85 @@
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 use std::{
-    fs,
+    fs, io,
     path::{Path, PathBuf},
     time::Duration,
 };
 use version_compare::Version;
 
-use crate::{github, path};
+use crate::{config, github, offline, path};
 
 const CACHE_DURATION: u64 = 8; // hours
 
-pub fn make_path(executable_name: &str) -> Result<PathBuf> {
-    let dir = path::data_dir()?.join("bin");
-    fs::create_dir_all(&dir)?;
+/// Where a tool is released and what it's called once installed
+pub struct Tool<'a> {
+    pub owner: &'a str,
+    pub repository: &'a str,
+    /// Release asset to download, `*` matches anything
+    pub asset_pattern: &'a str,
+    pub executable_name: &'a str,
+}
 
-    let suffix = if cfg!(target_os = "windows") {
-        ".exe"
-    } else {
-        ""
-    };
+/// Directory the managed tools are installed into
+pub fn bin_dir() -> Result<PathBuf> {
+    Ok(path::data_dir()?.join("bin"))
+}
 
-    Ok(dir.join(format!("{executable_name}{suffix}")))
+/// [`meta_path`] inside the data dir, creating the meta dir if needed
+fn data_meta_path(executable_name: &str, kind: &str) -> Result<PathBuf> {
+    let path = meta_path(&path::data_dir()?, executable_name, kind);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    Ok(path)
 }
 
 pub fn version_path(executable_name: &str) -> Result<PathBuf> {
-    let dir = path::data_dir()?.join("meta");
+    data_meta_path(executable_name, "version")
+}
 
-    fs::create_dir_all(&dir)?;
+/// Exists while an install of the tool is being swapped in, left behind if that got interrupted
+fn pending_path(data_dir: &Path, executable_name: &str) -> PathBuf {
+    meta_path(data_dir, executable_name, "pending")
+}
+
+/// Where a new version is unpacked before it replaces the installed one
+fn staging_dir(data_dir: &Path, executable_name: &str) -> PathBuf {
+    data_dir.join("staging").join(executable_name)
+}
+
+/// Where the previously installed version is kept
+fn rollback_dir(data_dir: &Path, executable_name: &str) -> PathBuf {
+    data_dir.join("rollback").join(executable_name)
+}
+
+/// Newest release seen the last time GitHub was asked
+fn latest_path(executable_name: &str) -> Result<PathBuf> {
+    data_meta_path(executable_name, "latest")
+}
 
-    Ok(dir.join(format!("{executable_name}_version")))
+pub fn latest_version(data_dir: &Path, executable_name: &str) -> Option<String> {
+    fs::read_to_string(meta_path(data_dir, executable_name, "latest")).ok()
+}
+
+fn meta_path(data_dir: &Path, executable_name: &str, kind: &str) -> PathBuf {
+    data_dir
+        .join("meta")
+        .join(format!("{executable_name}_{kind}"))
+}
+
+/// SHA-256 of the asset the installed version was extracted from
+pub fn digest_path(executable_name: &str) -> Result<PathBuf> {
+    data_meta_path(executable_name, "sha256")
 }
 
 pub fn version(executable_name: &str) -> Result<String> {
-    let file = version_path(executable_name)?;
+    version_in(&path::data_dir()?, executable_name)
+}
+
+pub fn version_in(data_dir: &Path, executable_name: &str) -> Result<String> {
+    let file = meta_path(data_dir, executable_name, "version");
 
     if !file.exists() {
         bail!("Version not found");
@@ -41,19 +88,42 @@ pub fn version(executable_name: &str) -> Result<String> {
     Ok(fs::read_to_string(file)?)
 }
 
-pub fn is_update_available<P>(
-    owner: &str,
-    repository: &str,
-    executable_name: &str,
+/// Tag the tool is pinned to, a pin in the project config takes precedence over the global one
+pub fn pinned_version(executable_name: &str, root_dir: Option<&Path>) -> Result<Option<String>> {
+    Ok(config::load(root_dir)?.pins.get(executable_name).cloned())
+}
+
+/// When to look for a new release
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Update {
+    /// Only ask GitHub once the last check is older than the cache duration
+    Cached,
+    /// Ask GitHub now
+    Check,
+    /// Download the release even if it's installed already
+    Force,
+}
+
+fn is_update_available<P>(
+    tool: &Tool,
     path_fn: P,
+    pin: Option<&str>,
+    use_cache: bool,
 ) -> Result<bool>
 where
     P: Fn() -> Result<PathBuf>,
 {
+    let executable_name = tool.executable_name;
+
     if !path_fn()?.exists() {
         return Ok(true);
     }
 
+    if pending_path(&path::data_dir()?, executable_name).exists() {
+        tracing::warn!("Previous install of {executable_name} was interrupted, reinstalling");
+        return Ok(true);
+    }
+
     if !version_path(executable_name)?.exists() {
         return Ok(true);
     }
@@ -64,9 +134,15 @@ where
 
     tracing::debug!("{executable_name} version {v} installed");
 
+    // pinned versions never change, no need to ask GitHub
+    if let Some(pin) = pin {
+        tracing::debug!("{executable_name} is pinned to {pin}");
+        return Ok(v != pin);
+    }
+
     // if the version file is younger than cache duration
     let last_modified = version_path(executable_name)?.metadata()?.modified()?;
-    if last_modified.elapsed()? < Duration::from_hours(CACHE_DURATION) {
+    if use_cache && last_modified.elapsed()? < Duration::from_hours(CACHE_DURATION) {
         return Ok(false);
     }
 
@@ -77,10 +153,12 @@ where
         return Ok(true);
     };
 
-    let release = github::fetch_release(owner, repository)?;
+    let release = github::fetch_release(tool.owner, tool.repository, None)?;
 
     tracing::debug!("{executable_name} version {} is newest", release.tag_name);
 
+    fs::write(latest_path(executable_name)?, &release.tag_name)?;
+
     let Some(current) = Version::from(&release.tag_name) else {
         return Ok(false);
     };
@@ -89,60 +167,206 @@ where
 }
 
 fn download_and_install<F, P>(
-    owner: &str,
-    repository: &str,
-    asset_name: &str,
-    executable_name: &str,
+    tool: &Tool,
     install_fn: F,
     path_fn: P,
-    force_redownload: bool,
+    update: Update,
+    root_dir: Option<&Path>,
 ) -> Result<PathBuf>
 where
-    F: Fn(&Path) -> Result<()>,
+    F: Fn(&Path, &Path) -> Result<()>,
     P: Fn() -> Result<PathBuf>,
 {
-    if !force_redownload && !is_update_available(owner, repository, executable_name, &path_fn)? {
+    let executable_name = tool.executable_name;
+    let pin = pinned_version(executable_name, root_dir)?;
+
+    if offline::is_enabled() {
+        let path = path_fn()?;
+
+        if !path.exists() {
+            bail!("{executable_name} is not installed and offline mode is enabled");
+        }
+
+        tracing::debug!("Offline mode is enabled, using installed {executable_name}");
+
+        return Ok(path);
+    }
+
+    if update != Update::Force
+        && !is_update_available(tool, &path_fn, pin.as_deref(), update == Update::Cached)?
+    {
         return path_fn();
     }
 
-    let (downloaded_file, release) = github::download_release(owner, repository, asset_name)?;
+    let (downloaded_file, release, verified_digest) = github::download_release(
+        tool.owner,
+        tool.repository,
+        pin.as_deref(),
+        tool.asset_pattern,
+    )?;
 
     tracing::debug!("New {executable_name} version found {}", release.tag_name);
 
-    install_fn(&downloaded_file)?;
+    if pin.is_none() {
+        fs::write(latest_path(executable_name)?, &release.tag_name)?;
+    }
+
+    let data_dir = path::data_dir()?;
+
+    let staging = staging_dir(&data_dir, executable_name);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
 
-    fs::write(version_path(executable_name)?, release.tag_name)?;
+    install_fn(&downloaded_file, &staging)?;
 
-    github::clear_downloads(owner, repository)?;
+    // only a digest that matched the published checksum says anything about the install
+    swap_in(
+        &data_dir,
+        executable_name,
+        &release.tag_name,
+        verified_digest.as_deref(),
+    )?;
+
+    github::clear_downloads(tool.owner, tool.repository)?;
 
     path_fn()
 }
 
-pub fn install_with<F, P>(
-    owner: &str,
-    repository: &str,
-    asset_name: &str,
+/// Writes to a sibling file first so readers never see a partially written file
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Replaces the installed files with the staged ones, moving the installed ones into the rollback dir.
+///
+/// Everything is renamed within the data dir, so each file is swapped atomically. A crash midway leaves
+/// the pending marker behind, which makes the next run reinstall the tool.
+fn swap_in(
+    data_dir: &Path,
     executable_name: &str,
+    version: &str,
+    digest: Option<&str>,
+) -> Result<()> {
+    let staging = staging_dir(data_dir, executable_name);
+    let bin = data_dir.join("bin");
+    let meta = data_dir.join("meta");
+    let rollback = rollback_dir(data_dir, executable_name);
+    let rollback_files = rollback.join("files");
+
+    let staged = fs::read_dir(&staging)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    if staged.is_empty() {
+        bail!("Nothing was staged for {executable_name}");
+    }
+
+    fs::create_dir_all(&bin)?;
+    fs::create_dir_all(&meta)?;
+
+    let pending = pending_path(data_dir, executable_name);
+    fs::write(&pending, version)?;
+
+    if rollback.exists() {
+        fs::remove_dir_all(&rollback)?;
+    }
+    fs::create_dir_all(&rollback_files)?;
+
+    let version_file = meta.join(format!("{executable_name}_version"));
+    let digest_file = meta.join(format!("{executable_name}_sha256"));
+
+    for (from, to) in [(&version_file, "version"), (&digest_file, "sha256")] {
+        if from.exists() {
+            fs::copy(from, rollback.join(to))?;
+        }
+    }
+
+    for file in staged {
+        let name = file.file_name().context("staged file has no name")?;
+        let installed = bin.join(name);
+
+        if installed.exists() {
+            fs::rename(&installed, rollback_files.join(name))?;
+        }
+
+        fs::rename(&file, &installed)?;
+    }
+
+    write_atomic(&version_file, version)?;
+
+    match digest {
+        Some(digest) => write_atomic(&digest_file, digest)?,
+        None if digest_file.exists() => fs::remove_file(&digest_file)?,
+        None => (),
+    }
+
+    fs::remove_file(&pending)?;
+    fs::remove_dir_all(&staging)?;
+
+    tracing::debug!("Installed {executable_name} {version}");
+
+    Ok(())
+}
+
+/// Swaps the previously installed version of a tool back in, returns the version it rolled back to
+pub fn rollback(executable_name: &str) -> Result<String> {
+    rollback_in(&path::data_dir()?, executable_name)
+}
+
+fn rollback_in(data_dir: &Path, executable_name: &str) -> Result<String> {
+    let rollback = rollback_dir(data_dir, executable_name);
+    let rollback_files = rollback.join("files");
+
+    let has_files = fs::read_dir(&rollback_files).is_ok_and(|mut dir| dir.next().is_some());
+    if !has_files {
+        bail!("No previous version of {executable_name} to roll back to");
+    }
+
+    let version = fs::read_to_string(rollback.join("version"))
+        .with_context(|| format!("No version recorded for the previous {executable_name}"))?;
+    let digest = fs::read_to_string(rollback.join("sha256")).ok();
+
+    let staging = staging_dir(data_dir, executable_name);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(staging.parent().context("staging dir has no parent")?)?;
+    fs::rename(&rollback_files, &staging)?;
+
+    swap_in(data_dir, executable_name, &version, digest.as_deref())?;
+
+    Ok(version)
+}
+
+/// Installs the newest release of `tool`, or the one it's pinned to for `root_dir`.
+///
+/// `install_fn` gets the downloaded asset and a staging dir to put the files meant for the bin dir into.
+pub fn install_with<F, P>(
+    tool: &Tool,
     install_fn: F,
     path_fn: P,
     force_redownload: bool,
+    root_dir: Option<&Path>,
 ) -> Result<PathBuf>
 where
-    F: Fn(&Path) -> Result<()>,
+    F: Fn(&Path, &Path) -> Result<()>,
     P: Fn() -> Result<PathBuf>,
 {
-    match download_and_install(
-        owner,
-        repository,
-        asset_name,
-        executable_name,
-        &install_fn,
-        &path_fn,
-        force_redownload,
-    ) {
+    let update = if force_redownload {
+        Update::Force
+    } else {
+        Update::Cached
+    };
+
+    match download_and_install(tool, &install_fn, &path_fn, update, root_dir) {
         Ok(path) => Ok(path),
         Err(err) => {
-            tracing::error!("Could not install {executable_name}: {err:?}");
+            tracing::error!("Could not install {}: {err:?}", tool.executable_name);
 
             // if file exists but we couldnt install just return it
             match path_fn()? {
@@ -154,3 +378,117 @@ where
         }
     }
 }
+
+/// Asks GitHub for a newer release of `tool` right away and installs it, unlike [`install_with`] errors
+/// are returned even if an older version is installed
+pub fn update<F, P>(
+    tool: &Tool,
+    install_fn: F,
+    path_fn: P,
+    root_dir: Option<&Path>,
+) -> Result<PathBuf>
+where
+    F: Fn(&Path, &Path) -> Result<()>,
+    P: Fn() -> Result<PathBuf>,
+{
+    if offline::is_enabled() {
+        bail!(
+            "Offline mode is enabled, can't check for updates of {}",
+            tool.executable_name
+        );
+    }
+
+    download_and_install(tool, &install_fn, &path_fn, Update::Check, root_dir)
+}
+
+/// Removes the version files and the rollback of a tool, returns whether there was anything to remove
+pub fn remove_meta(data_dir: &Path, executable_name: &str) -> Result<bool> {
+    let files = [
+        meta_path(data_dir, executable_name, "version"),
+        meta_path(data_dir, executable_name, "sha256"),
+        meta_path(data_dir, executable_name, "latest"),
+        pending_path(data_dir, executable_name),
+    ];
+
+    let dirs = [
+        staging_dir(data_dir, executable_name),
+        rollback_dir(data_dir, executable_name),
+    ];
+
+    let mut removed = false;
+
+    for file in files.iter().filter(|f| f.exists()) {
+        fs::remove_file(file)?;
+        removed = true;
+    }
+
+    for dir in dirs.iter().filter(|d| d.exists()) {
+        fs::remove_dir_all(dir)?;
+        removed = true;
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::release_downloader::{pending_path, rollback_in, staging_dir, swap_in};
+    use pretty_assertions::assert_eq;
+    use std::{env, fs, path::Path};
+
+    fn stage(data_dir: &Path, contents: &str) {
+        let staging = staging_dir(data_dir, "tool");
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(staging.join("tool"), contents).unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_swap_in_and_rollback() {
+        let data_dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("swap-in-{}", std::process::id()));
+
+        let bin = data_dir.join("bin").join("tool");
+        let version = data_dir.join("meta").join("tool_version");
+
+        assert!(rollback_in(&data_dir, "tool").is_err());
+
+        stage(&data_dir, "first");
+        swap_in(&data_dir, "tool", "v1", Some("aaa")).unwrap();
+
+        assert_eq!("first", read(&bin));
+        assert_eq!("v1", read(&version));
+        assert!(!pending_path(&data_dir, "tool").exists());
+        assert!(!staging_dir(&data_dir, "tool").exists());
+
+        // nothing was installed before the first version
+        assert!(rollback_in(&data_dir, "tool").is_err());
+
+        stage(&data_dir, "second");
+        swap_in(&data_dir, "tool", "v2", Some("bbb")).unwrap();
+
+        assert_eq!("second", read(&bin));
+        assert_eq!("v2", read(&version));
+
+        assert_eq!("v1", rollback_in(&data_dir, "tool").unwrap());
+        assert_eq!("first", read(&bin));
+        assert_eq!("v1", read(&version));
+        assert_eq!("aaa", read(&data_dir.join("meta").join("tool_sha256")));
+
+        // rolling back again undoes the rollback
+        assert_eq!("v2", rollback_in(&data_dir, "tool").unwrap());
+        assert_eq!("second", read(&bin));
+
+        // an unverified download must not leave the previous digest behind
+        stage(&data_dir, "third");
+        swap_in(&data_dir, "tool", "v3", None).unwrap();
+        assert_eq!("v3", read(&version));
+        assert!(!data_dir.join("meta").join("tool_sha256").exists());
+
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// A request received by `serve`, the request line and headers are lowercased
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub head: String,
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+impl Request {
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key == name).then(|| value.trim())
+        })
+    }
+}
+
+pub struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Response {
+    #[must_use]
+    pub fn ok(body: impl Into<String>) -> Self {
+        Self::status("200 OK", body)
+    }
+
+    #[must_use]
+    pub fn not_found() -> Self {
+        Self::status("404 Not Found", "")
+    }
+
+    #[must_use]
+    pub fn status(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+/// Plain HTTP stand-in answering every request with `handler`, returns its base url and the
+/// requests it received
+pub fn serve<F>(handler: F) -> (String, Arc<Mutex<Vec<Request>>>)
+where
+    F: Fn(&Request) -> Response + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let recorded = requests.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut head = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                head.push_str(&line.to_lowercase());
+            }
+
+            let mut request_line = head.split_whitespace();
+            let mut request = Request {
+                method: request_line.next().unwrap_or_default().to_string(),
+                path: request_line.next().unwrap_or_default().to_string(),
+                head: head.clone(),
+                body: String::new(),
+            };
+
+            let content_length = request
+                .header("content-length")
+                .map_or(0, |len| len.parse().unwrap());
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            request.body = String::from_utf8(body).unwrap();
+
+            let response = handler(&request);
+            recorded.lock().unwrap().push(request);
+
+            let mut raw = format!("HTTP/1.1 {}\r\n", response.status);
+            for (name, value) in &response.headers {
+                raw.push_str(&format!("{name}: {value}\r\n"));
+            }
+            raw.push_str(&format!(
+                "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.body.len(),
+                response.body
+            ));
+
+            stream.write_all(raw.as_bytes()).unwrap();
+        }
+    });
+
+    (url, requests)
+}
+
+/// Serves fixed bodies per path, everything else is not found
+pub fn serve_routes(routes: Vec<(String, String)>) -> (String, Arc<Mutex<Vec<Request>>>) {
+    serve(move |request| {
+        routes
+            .iter()
+            .find(|(route, _)| *route == request.path)
+            .map_or_else(Response::not_found, |(_, body)| Response::ok(body.clone()))
+    })
+}
@@ -0,0 +1,682 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    release_downloader::{self, Tool},
+    utils,
+};
+
+const DEFAULT_MANIFEST: &str = include_str!("../assets/tools.toml");
+
+/// A tool installed from GitHub releases
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub owner: String,
+    pub repository: String,
+
+    /// Release asset per platform, either `<os>-<arch>` or just `<os>`
+    pub assets: HashMap<String, AssetSpec>,
+
+    /// Archive format, guessed from the asset name if not set
+    pub archive: Option<ArchiveType>,
+
+    /// Executable relative to the archive root, defaults to the tool name
+    pub executable: Option<String>,
+
+    /// Part of the archive to install, defaults to the executable. "." installs all of it
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AssetSpec {
+    Name(String),
+    Detailed {
+        asset: String,
+        archive: Option<ArchiveType>,
+        executable: Option<String>,
+        path: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveType {
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    Zip,
+    Dmg,
+    /// The asset is the executable itself
+    Binary,
+}
+
+impl ArchiveType {
+    fn guess(asset: &str) -> Self {
+        if asset.ends_with(".tar.gz") || asset.ends_with(".tgz") {
+            Self::TarGz
+        } else if asset.ends_with(".zip") {
+            Self::Zip
+        } else if asset.ends_with(".dmg") {
+            Self::Dmg
+        } else {
+            Self::Binary
+        }
+    }
+}
+
+/// What is known about an installed tool
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolStatus {
+    pub name: String,
+    pub installed_version: Option<String>,
+
+    /// Newest release seen the last time GitHub was asked
+    pub latest_version: Option<String>,
+
+    /// Path of the executable
+    pub path: PathBuf,
+
+    /// Size on disk in bytes, nothing if not installed
+    pub size: Option<u64>,
+}
+
+/// A tools asset for one platform with all defaults filled in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedAsset {
+    pub asset: String,
+    pub archive: ArchiveType,
+    pub executable: String,
+    pub path: String,
+}
+
+impl ToolSpec {
+    /// Asset for the current platform
+    pub fn resolve(&self, name: &str) -> Result<ResolvedAsset> {
+        self.resolve_for(name, env::consts::OS, env::consts::ARCH)
+    }
+
+    fn resolve_for(&self, name: &str, os: &str, arch: &str) -> Result<ResolvedAsset> {
+        let Some(spec) = self
+            .assets
+            .get(&format!("{os}-{arch}"))
+            .or_else(|| self.assets.get(os))
+        else {
+            bail!("{name} has no release asset for {os}-{arch}");
+        };
+
+        let (asset, archive, executable, path) = match spec {
+            AssetSpec::Name(asset) => (asset, None, None, None),
+            AssetSpec::Detailed {
+                asset,
+                archive,
+                executable,
+                path,
+            } => (asset, *archive, executable.as_ref(), path.as_ref()),
+        };
+
+        let executable = executable
+            .or(self.executable.as_ref())
+            .cloned()
+            .unwrap_or_else(|| {
+                if os == "windows" {
+                    format!("{name}.exe")
+                } else {
+                    name.to_string()
+                }
+            });
+
+        Ok(ResolvedAsset {
+            asset: asset.clone(),
+            archive: archive
+                .or(self.archive)
+                .unwrap_or_else(|| ArchiveType::guess(asset)),
+            path: path
+                .or(self.path.as_ref())
+                .cloned()
+                .unwrap_or_else(|| executable.clone()),
+            executable,
+        })
+    }
+}
+
+impl ResolvedAsset {
+    /// File or directory the asset is installed as inside the bin dir
+    fn install_name(&self, name: &str) -> Result<String> {
+        if self.path == "." {
+            return Ok(name.to_string());
+        }
+
+        Path::new(&self.path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(str::to_string)
+            .with_context(|| format!("invalid path {} for {name}", self.path))
+    }
+
+    /// Where the executable ends up when the asset is installed into `dir`
+    fn executable_in(&self, dir: &Path, name: &str) -> Result<PathBuf> {
+        let installed = dir.join(self.install_name(name)?);
+
+        let relative = if self.path == "." {
+            Path::new(&self.executable)
+        } else {
+            Path::new(&self.executable)
+                .strip_prefix(&self.path)
+                .with_context(|| {
+                    format!(
+                        "executable {} of {name} is not inside {}",
+                        self.executable, self.path
+                    )
+                })?
+        };
+
+        if relative.as_os_str().is_empty() {
+            return Ok(installed);
+        }
+
+        Ok(installed.join(relative))
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn parse_manifest(
+    default: &str,
+    overrides: BTreeMap<String, ToolSpec>,
+) -> Result<BTreeMap<String, ToolSpec>> {
+    let mut tools: BTreeMap<String, ToolSpec> =
+        toml::from_str(default).context("could not parse tool manifest")?;
+
+    tools.extend(overrides);
+
+    if let Some(name) = tools.keys().find(|name| !is_valid_name(name)) {
+        bail!("Invalid tool name {name:?}");
+    }
+
+    Ok(tools)
+}
+
+/// All known tools, the defaults merged with the ones from the global config
+pub fn manifest() -> Result<BTreeMap<String, ToolSpec>> {
+    parse_manifest(DEFAULT_MANIFEST, config::load(None)?.tools)
+}
+
+pub fn spec(name: &str) -> Result<ToolSpec> {
+    manifest()?
+        .remove(name)
+        .with_context(|| format!("Unknown tool {name}"))
+}
+
+/// Where the executable of the tool is, whether it is installed or not
+pub fn path(name: &str) -> Result<PathBuf> {
+    spec(name)?
+        .resolve(name)?
+        .executable_in(&release_downloader::bin_dir()?, name)
+}
+
+/// Installs the tool, respecting the version pinned for `root_dir` if any
+pub fn install(name: &str, root_dir: Option<&Path>) -> Result<PathBuf> {
+    install_with(name, false, root_dir)
+}
+
+pub fn install_with(
+    name: &str,
+    force_redownload: bool,
+    root_dir: Option<&Path>,
+) -> Result<PathBuf> {
+    let spec = spec(name)?;
+    let resolved = spec.resolve(name)?;
+    let bin_dir = release_downloader::bin_dir()?;
+
+    release_downloader::install_with(
+        &tool(name, &spec, &resolved),
+        |downloaded_file, staging_dir| unpack(downloaded_file, staging_dir, name, &resolved),
+        || {
+            fs::create_dir_all(&bin_dir)?;
+            resolved.executable_in(&bin_dir, name)
+        },
+        force_redownload,
+        root_dir,
+    )
+}
+
+/// Installs a newer release of the tool if there is one, returns the version it was updated to
+pub fn update(name: &str, root_dir: Option<&Path>) -> Result<Option<String>> {
+    let spec = spec(name)?;
+    let resolved = spec.resolve(name)?;
+    let bin_dir = release_downloader::bin_dir()?;
+
+    let before = release_downloader::version(name).ok();
+
+    release_downloader::update(
+        &tool(name, &spec, &resolved),
+        |downloaded_file, staging_dir| unpack(downloaded_file, staging_dir, name, &resolved),
+        || {
+            fs::create_dir_all(&bin_dir)?;
+            resolved.executable_in(&bin_dir, name)
+        },
+        root_dir,
+    )?;
+
+    let after = release_downloader::version(name).ok();
+
+    Ok(after.filter(|after| before.as_ref() != Some(after)))
+}
+
+/// Removes the tool along with its version files and the previous version kept for rollback
+pub fn uninstall(name: &str) -> Result<()> {
+    uninstall_in(&crate::path::data_dir()?, name, &spec(name)?)
+}
+
+fn uninstall_in(data_dir: &Path, name: &str, spec: &ToolSpec) -> Result<()> {
+    let resolved = spec.resolve(name)?;
+    let installed = data_dir.join("bin").join(resolved.install_name(name)?);
+
+    let mut removed = installed.exists();
+
+    if installed.is_dir() {
+        fs::remove_dir_all(&installed)?;
+    } else if removed {
+        fs::remove_file(&installed)?;
+    }
+
+    removed |= release_downloader::remove_meta(data_dir, name)?;
+
+    if !removed {
+        bail!("{name} is not installed");
+    }
+
+    Ok(())
+}
+
+/// Every tool in the manifest that has a release for this platform
+pub fn list() -> Result<Vec<ToolStatus>> {
+    list_in(&crate::path::data_dir()?, manifest()?)
+}
+
+fn list_in(data_dir: &Path, manifest: BTreeMap<String, ToolSpec>) -> Result<Vec<ToolStatus>> {
+    let bin_dir = data_dir.join("bin");
+    let mut tools = Vec::new();
+
+    for (name, spec) in manifest {
+        let resolved = match spec.resolve(&name) {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                tracing::debug!("Skipping {name}: {err:?}");
+                continue;
+            }
+        };
+
+        let installed = bin_dir.join(resolved.install_name(&name)?);
+        let size = if installed.exists() {
+            Some(utils::disk_size(&installed)?)
+        } else {
+            None
+        };
+
+        tools.push(ToolStatus {
+            installed_version: size
+                .and_then(|_| release_downloader::version_in(data_dir, &name).ok()),
+            latest_version: release_downloader::latest_version(data_dir, &name),
+            path: resolved.executable_in(&bin_dir, &name)?,
+            size,
+            name,
+        });
+    }
+
+    Ok(tools)
+}
+
+fn tool<'a>(name: &'a str, spec: &'a ToolSpec, resolved: &'a ResolvedAsset) -> Tool<'a> {
+    Tool {
+        owner: &spec.owner,
+        repository: &spec.repository,
+        asset_pattern: &resolved.asset,
+        executable_name: name,
+    }
+}
+
+/// Restores the previously installed version of the tool
+pub fn rollback(name: &str) -> Result<String> {
+    spec(name)?;
+    release_downloader::rollback(name)
+}
+
+/// Puts the installable part of the downloaded asset into the staging dir
+fn unpack(
+    downloaded_file: &Path,
+    staging_dir: &Path,
+    name: &str,
+    asset: &ResolvedAsset,
+) -> Result<()> {
+    let target = staging_dir.join(asset.install_name(name)?);
+
+    match asset.archive {
+        ArchiveType::Binary => utils::move_file(downloaded_file, &target)?,
+        ArchiveType::TarGz | ArchiveType::Zip => {
+            let unpacked = downloaded_file
+                .parent()
+                .context("could not get parent dir")?
+                .join("unpacked");
+
+            if unpacked.exists() {
+                fs::remove_dir_all(&unpacked)?;
+            }
+            fs::create_dir_all(&unpacked)?;
+
+            let file = File::open(downloaded_file)?;
+
+            if asset.archive == ArchiveType::TarGz {
+                tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(&unpacked)?;
+            } else {
+                zip::ZipArchive::new(file)?.extract(&unpacked)?;
+            }
+
+            let source = if asset.path == "." {
+                unpacked
+            } else {
+                unpacked.join(&asset.path)
+            };
+
+            if !source.exists() {
+                bail!(
+                    "{} doesnt exist after unpacking {}",
+                    asset.path,
+                    asset.asset
+                );
+            }
+
+            utils::move_file(&source, &target)?;
+        }
+        ArchiveType::Dmg => unpack_dmg(downloaded_file, &target, asset)?,
+    }
+
+    let executable = asset.executable_in(staging_dir, name)?;
+
+    if !executable.exists() {
+        bail!(
+            "{} doesnt exist after unpacking {}",
+            asset.executable,
+            asset.asset
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+        fs::set_permissions(&executable, Permissions::from_mode(0o700))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn unpack_dmg(downloaded_file: &Path, target: &Path, asset: &ResolvedAsset) -> Result<()> {
+    use dmg::Attach;
+    use fs_extra::dir;
+
+    let handle = Attach::new(downloaded_file).with()?;
+
+    tracing::debug!("Mounted .dmg at {:?}", handle.mount_point);
+
+    let source = handle.mount_point.join(&asset.path);
+
+    if source.is_dir() {
+        dir::copy(
+            &source,
+            target,
+            &dir::CopyOptions::new().overwrite(true).copy_inside(true),
+        )?;
+    } else {
+        fs::copy(&source, target)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn unpack_dmg(_downloaded_file: &Path, _target: &Path, asset: &ResolvedAsset) -> Result<()> {
+    bail!(
+        "{} is a disk image, those can only be installed on macOS",
+        asset.asset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, env, fs, path::Path};
+
+    use crate::tools::{
+        ArchiveType, AssetSpec, DEFAULT_MANIFEST, ResolvedAsset, ToolSpec, list_in, parse_manifest,
+        uninstall_in, unpack,
+    };
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_default_manifest() {
+        let tools = parse_manifest(DEFAULT_MANIFEST, BTreeMap::new()).unwrap();
+
+        let mobdap = tools["mobdap"]
+            .resolve_for("mobdap", "windows", "x86_64")
+            .unwrap();
+        assert_eq!(
+            ResolvedAsset {
+                asset: "mobdap-windows-amd64.zip".to_string(),
+                archive: ArchiveType::Zip,
+                executable: "mobdap.exe".to_string(),
+                path: "mobdap.exe".to_string(),
+            },
+            mobdap
+        );
+
+        let neovide = tools["neovide"]
+            .resolve_for("neovide", "macos", "aarch64")
+            .unwrap();
+        assert_eq!(ArchiveType::Dmg, neovide.archive);
+        assert_eq!(
+            Path::new("bin/Neovide.app/Contents/MacOS/neovide"),
+            neovide.executable_in(Path::new("bin"), "neovide").unwrap()
+        );
+
+        let bridge = tools["defold-nvim-bridge"]
+            .resolve_for("defold-nvim-bridge", "linux", "aarch64")
+            .unwrap();
+        assert_eq!(ArchiveType::Binary, bridge.archive);
+        assert_eq!(
+            Path::new("bin/defold-nvim-bridge"),
+            bridge
+                .executable_in(Path::new("bin"), "defold-nvim-bridge")
+                .unwrap()
+        );
+
+        assert!(
+            tools["mobdap"]
+                .resolve_for("mobdap", "freebsd", "x86_64")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_user_tools_replace_defaults() {
+        let overrides: BTreeMap<String, ToolSpec> = toml::from_str(
+            r#"
+[mobdap]
+owner = "someone"
+repository = "mobdap-fork"
+assets = { linux = "mobdap.tar.gz" }
+
+[lua-language-server]
+owner = "LuaLS"
+repository = "lua-language-server"
+path = "."
+executable = "bin/lua-language-server"
+assets = { linux-x86_64 = "lua-language-server-*-linux-x64.tar.gz" }
+"#,
+        )
+        .unwrap();
+
+        let tools = parse_manifest(DEFAULT_MANIFEST, overrides).unwrap();
+
+        assert_eq!("someone", tools["mobdap"].owner);
+        assert!(
+            tools["mobdap"]
+                .resolve_for("mobdap", "macos", "aarch64")
+                .is_err()
+        );
+
+        let luals = tools["lua-language-server"]
+            .resolve_for("lua-language-server", "linux", "x86_64")
+            .unwrap();
+        assert_eq!(
+            Path::new("bin/lua-language-server/bin/lua-language-server"),
+            luals
+                .executable_in(Path::new("bin"), "lua-language-server")
+                .unwrap()
+        );
+
+        let invalid: BTreeMap<String, ToolSpec> = toml::from_str(
+            r#"
+["../evil"]
+owner = "someone"
+repository = "evil"
+assets = {}
+"#,
+        )
+        .unwrap();
+
+        assert!(parse_manifest(DEFAULT_MANIFEST, invalid).is_err());
+    }
+
+    #[test]
+    fn test_unpack_directory_from_tarball() {
+        let dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("unpack-{}", std::process::id()));
+        let content = dir.join("content");
+        fs::create_dir_all(content.join("bin")).unwrap();
+        fs::write(content.join("bin").join("tool"), "#!/bin/sh\n").unwrap();
+        fs::write(content.join("main.lua"), "").unwrap();
+
+        let download = dir.join("download");
+        fs::create_dir_all(&download).unwrap();
+        let archive = download.join("tool.tar.gz");
+
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        builder.append_dir_all(".", &content).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let staging = dir.join("staging");
+        fs::create_dir_all(&staging).unwrap();
+
+        let asset = ResolvedAsset {
+            asset: "tool.tar.gz".to_string(),
+            archive: ArchiveType::TarGz,
+            executable: "bin/tool".to_string(),
+            path: ".".to_string(),
+        };
+
+        unpack(&archive, &staging, "tool", &asset).unwrap();
+
+        let installed = staging.join("tool");
+        let executable_exists = installed.join("bin").join("tool").exists();
+        let rest_exists = installed.join("main.lua").exists();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(executable_exists);
+        assert!(rest_exists);
+    }
+
+    #[test]
+    fn test_list_and_uninstall() {
+        let data_dir = env::temp_dir()
+            .join("defold.nvim-tests")
+            .join(format!("tools-{}", std::process::id()));
+        let bin = data_dir.join("bin");
+        let meta = data_dir.join("meta");
+        fs::create_dir_all(&bin).unwrap();
+        fs::create_dir_all(&meta).unwrap();
+
+        let spec = ToolSpec {
+            owner: "me".to_string(),
+            repository: "tool".to_string(),
+            assets: [(
+                env::consts::OS.to_string(),
+                AssetSpec::Name("tool".to_string()),
+            )]
+            .into(),
+            archive: None,
+            executable: None,
+            path: None,
+        };
+        let executable = |name: &str| bin.join(spec.resolve(name).unwrap().executable);
+        let manifest = BTreeMap::from([
+            ("tool".to_string(), spec.clone()),
+            ("other".to_string(), spec.clone()),
+        ]);
+
+        fs::write(executable("tool"), "binary").unwrap();
+        fs::write(meta.join("tool_version"), "v1.0.0").unwrap();
+        fs::write(meta.join("tool_latest"), "v1.1.0").unwrap();
+        fs::write(meta.join("tool_sha256"), "aaa").unwrap();
+        fs::write(meta.join("other_latest"), "v2.0.0").unwrap();
+
+        let status = |name: &str| {
+            list_in(&data_dir, manifest.clone())
+                .unwrap()
+                .into_iter()
+                .find(|tool| tool.name == name)
+                .unwrap()
+        };
+
+        let tool = status("tool");
+        assert_eq!(Some("v1.0.0".to_string()), tool.installed_version);
+        assert_eq!(Some("v1.1.0".to_string()), tool.latest_version);
+        assert_eq!(executable("tool"), tool.path);
+        assert_eq!(Some(6), tool.size);
+
+        let other = status("other");
+        assert_eq!(None, other.installed_version);
+        assert_eq!(Some("v2.0.0".to_string()), other.latest_version);
+        assert_eq!(None, other.size);
+
+        uninstall_in(&data_dir, "tool", &spec).unwrap();
+
+        assert!(!executable("tool").exists());
+        assert!(!meta.join("tool_version").exists());
+        assert!(!meta.join("tool_latest").exists());
+        assert!(!meta.join("tool_sha256").exists());
+        assert!(meta.join("other_latest").exists());
+
+        let tool = status("tool");
+        assert_eq!(None, tool.installed_version);
+        assert_eq!(None, tool.latest_version);
+
+        assert_eq!(
+            "tool is not installed",
+            uninstall_in(&data_dir, "tool", &spec)
+                .unwrap_err()
+                .to_string()
+        );
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+}
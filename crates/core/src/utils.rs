@@ -1,16 +1,26 @@
 use std::{
     env::temp_dir,
+    fmt,
     fs::{self, File},
     io,
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use base64::{Engine as _, prelude::BASE64_STANDARD};
 use fs_extra::{dir, file};
+use reqwest::{
+    StatusCode,
+    header::{ETAG, HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
 use sha3::{Digest, Sha3_256};
 use url::Url;
 use walkdir::WalkDir;
 
+use crate::offline;
+
 #[must_use]
 pub fn sha3(str: &str) -> String {
     let mut hasher = Sha3_256::new();
@@ -20,18 +30,85 @@ pub fn sha3(str: &str) -> String {
     hex::encode(result)
 }
 
-pub fn project_id(root_dir: &str) -> Result<String> {
-    Ok(sha3(root_dir)
-        .get(0..8)
-        .context("could not create project id")?
-        .to_string())
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hex::encode(hasher.finalize()))
 }
 
-pub fn classname(root_dir: &str) -> Result<String> {
-    Ok(format!("com.defold.nvim.{}", project_id(root_dir)?))
+/// HTTP validators of a downloaded file, sent along to only download it again if it changed
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
 }
 
-pub fn download(url: &str) -> Result<PathBuf> {
+impl Validators {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string)
+        };
+
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+}
+
+/// Sent along with a download, never shown in logs
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer(String),
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &"<redacted>")
+                .finish(),
+            Self::Bearer(_) => f.debug_tuple("Bearer").field(&"<redacted>").finish(),
+        }
+    }
+}
+
+impl Credentials {
+    /// Value of the `Authorization` header, for clients that can't be handed the credentials
+    #[must_use]
+    pub fn authorization(&self) -> String {
+        match self {
+            Self::Basic { username, password } => format!(
+                "Basic {}",
+                BASE64_STANDARD.encode(format!(
+                    "{username}:{}",
+                    password.as_deref().unwrap_or_default()
+                ))
+            ),
+            Self::Bearer(token) => format!("Bearer {token}"),
+        }
+    }
+}
+
+fn download_path(url: &str) -> Result<PathBuf> {
     let download_dir = temp_dir()
         .join("defold.nvim")
         .join("download")
@@ -45,13 +122,68 @@ pub fn download(url: &str) -> Result<PathBuf> {
         .and_then(std::iter::Iterator::last)
         .unwrap_or("file");
 
-    let download_file = download_dir.join(filename);
+    Ok(download_dir.join(filename))
+}
+
+pub fn download(url: &str) -> Result<PathBuf> {
+    let download_file = download_path(url)?;
 
     download_to(url, &download_file)?;
 
     Ok(download_file)
 }
 
+/// Downloads `url` unless it didn't change since `validators` were received, returns the file and
+/// the validators of the new version. Errors don't contain the url, it may contain secrets
+pub fn download_if_changed(
+    url: &str,
+    credentials: Option<&Credentials>,
+    validators: &Validators,
+) -> Result<Option<(PathBuf, Validators)>> {
+    if offline::is_enabled() {
+        bail!("Offline mode is enabled, not downloading anything");
+    }
+
+    let mut req = reqwest::blocking::Client::new().get(url);
+
+    match credentials {
+        Some(Credentials::Basic { username, password }) => {
+            req = req.basic_auth(username, password.as_ref());
+        }
+        Some(Credentials::Bearer(token)) => req = req.bearer_auth(token),
+        None => {}
+    }
+
+    if let Some(etag) = &validators.etag {
+        req = req.header(IF_NONE_MATCH, etag);
+    }
+
+    if let Some(last_modified) = &validators.last_modified {
+        req = req.header(IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let mut res = req.send().map_err(reqwest::Error::without_url)?;
+
+    if res.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    res.error_for_status_ref()
+        .map_err(|err| err.without_url())?;
+
+    let download_file = download_path(url)?;
+
+    tracing::debug!("Downloading to {}...", download_file.display());
+
+    let mut file = File::create(&download_file)?;
+    io::copy(&mut res, &mut file)?;
+
+    Ok(Some((
+        download_file,
+        Validators::from_headers(res.headers()),
+    )))
+}
+
 pub fn clear_download(url: &str) -> Result<()> {
     let download_root_dir = temp_dir().join("defold.nvim").join("download");
     let download_dir = download_root_dir.join(sha3(url).get(..8).context("could not make hash")?);
@@ -61,6 +193,8 @@ pub fn clear_download(url: &str) -> Result<()> {
 }
 
 pub fn download_to(url: &str, path: &Path) -> Result<()> {
+    offline::ensure_online(url)?;
+
     tracing::debug!("Downloading {url} to {}...", path.display());
 
     let mut res = reqwest::blocking::get(url)?;
@@ -109,6 +243,120 @@ pub fn move_file(from: &Path, to: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Size of a file or of all files in a directory in bytes
+pub fn disk_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+
+    for entry in WalkDir::new(path) {
+        let entry = entry?;
+
+        if entry.file_type().is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+
+    Ok(size)
+}
+
+/// Matches `name` against `pattern`, where `*` matches any run of characters
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    let Some(mut rest) = name.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        let Some(index) = rest.find(part) else {
+            return false;
+        };
+
+        rest = &rest[index + part.len()..];
+    }
+
+    rest.ends_with(last)
+}
+
 pub fn version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::{Response, serve},
+        utils::{Credentials, Validators, clear_download, download_if_changed, glob_match},
+    };
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(
+            "mobdap-linux-amd64.tar.gz",
+            "mobdap-linux-amd64.tar.gz"
+        ));
+        assert!(!glob_match(
+            "mobdap-linux-amd64.tar.gz",
+            "mobdap-linux-arm64.tar.gz"
+        ));
+        assert!(glob_match(
+            "lua-*-linux-x64.tar.gz",
+            "lua-3.15.0-linux-x64.tar.gz"
+        ));
+        assert!(!glob_match(
+            "lua-*-linux-x64.tar.gz",
+            "lua-3.15.0-linux-x64.tar.gz.sha256"
+        ));
+        assert!(glob_match("*-x64*", "lua-linux-x64.zip"));
+        assert!(!glob_match("a*b*c", "acb"));
+    }
+
+    #[test]
+    fn test_authorization() {
+        assert_eq!(
+            "Basic bWU6c2VjcmV0",
+            Credentials::Basic {
+                username: "me".to_string(),
+                password: Some("secret".to_string())
+            }
+            .authorization()
+        );
+        assert_eq!(
+            "Bearer secret",
+            Credentials::Bearer("secret".to_string()).authorization()
+        );
+    }
+
+    #[test]
+    fn test_download_if_changed() {
+        let (base_url, _) = serve(|request| {
+            if request.header("if-none-match") == Some("\"v1\"") {
+                Response::status("304 Not Modified", "")
+            } else {
+                Response::ok("v1").header("ETag", "\"v1\"")
+            }
+        });
+        let url = format!("{base_url}/master.zip");
+
+        let (file, validators) = download_if_changed(&url, None, &Validators::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!("v1", fs::read_to_string(file).unwrap());
+        assert_eq!(Some("\"v1\"".to_string()), validators.etag);
+
+        assert!(
+            download_if_changed(&url, None, &validators)
+                .unwrap()
+                .is_none()
+        );
+
+        clear_download(&url).unwrap();
+    }
+}
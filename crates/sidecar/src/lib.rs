@@ -1,5 +1,8 @@
 use anyhow::Context;
-use defold_nvim_core::{bridge, editor, editor_config, mobdap, nvim_server, path, project, utils};
+use defold_nvim_core::instances::{self, Instance};
+use defold_nvim_core::{
+    bridge, editor, editor_config, nvim_server, offline, path, project, tools, utils,
+};
 use defold_nvim_core::{focus, game_project::GameProject};
 use mlua::Value;
 use mlua::prelude::*;
@@ -89,14 +92,22 @@ fn register_exports(lua: &Lua) -> LuaResult<LuaTable> {
         "set_default_editor",
         lua.create_function(set_default_editor)?,
     )?;
+    exports.set(
+        "unset_default_editor",
+        lua.create_function(unset_default_editor)?,
+    )?;
     exports.set("find_bridge_path", lua.create_function(find_bridge_path)?)?;
     exports.set(
         "resolve_nvim_server_addr",
         lua.create_function(resolve_nvim_server_addr)?,
     )?;
+    exports.set(
+        "register_nvim_instance",
+        lua.create_function(register_nvim_instance)?,
+    )?;
     exports.set("focus_neovim", lua.create_function(focus_neovim)?)?;
     exports.set("focus_game", lua.create_function(focus_game)?)?;
-    exports.set("mobdap_install", lua.create_function(mobdap_install)?)?;
+    exports.set("install_tool", lua.create_function(install_tool)?)?;
     exports.set(
         "install_dependencies",
         lua.create_function(install_dependencies)?,
@@ -105,6 +116,8 @@ fn register_exports(lua: &Lua) -> LuaResult<LuaTable> {
         "list_dependency_dirs",
         lua.create_function(list_dependency_dirs)?,
     )?;
+    exports.set("is_offline", lua.create_function(is_offline)?)?;
+    exports.set("offline_status", lua.create_function(offline_status)?)?;
     exports.set("data_dir", lua.create_function(data_dir)?)?;
     exports.set("cache_dir", lua.create_function(cache_dir)?)?;
 
@@ -167,10 +180,31 @@ fn send_command(_lua: &Lua, (port, cmd): (u16, String)) -> LuaResult<()> {
 #[instrument(level = "debug", err(Debug), skip_all)]
 fn set_default_editor(
     lua: &Lua,
-    (port, plugin_root, launcher_settings): (u16, String, LuaValue),
+    (port, game_root, plugin_root, launcher_settings, focus_settings): (
+        u16,
+        String,
+        String,
+        LuaValue,
+        LuaValue,
+    ),
 ) -> LuaResult<()> {
     let launcher_settings = lua.from_value(launcher_settings)?;
-    editor_config::set_default_editor(port, &PathBuf::from(plugin_root), &launcher_settings)?;
+    let focus_settings = lua.from_value(focus_settings)?;
+
+    editor_config::set_default_editor(
+        port,
+        &absolute(game_root)?,
+        &PathBuf::from(plugin_root),
+        &launcher_settings,
+        &focus_settings,
+    )?;
+
+    Ok(())
+}
+
+#[instrument(level = "debug", err(Debug), skip_all)]
+fn unset_default_editor(_lua: &Lua, (port, game_root): (u16, String)) -> LuaResult<()> {
+    editor_config::unset_default_editor(port, &absolute(game_root)?)?;
 
     Ok(())
 }
@@ -201,28 +235,64 @@ fn resolve_nvim_server_addr(
 }
 
 #[instrument(level = "debug", err(Debug), skip_all)]
-fn focus_neovim(_lua: &Lua, game_root: String) -> LuaResult<()> {
-    focus::focus_neovim(absolute(game_root)?)?;
+fn register_nvim_instance(_lua: &Lua, (game_root, addr): (String, String)) -> LuaResult<()> {
+    instances::register(&Instance {
+        pid: std::process::id(),
+        address: addr,
+        launcher: "nvim".to_string(),
+        root_dir: absolute(game_root)?,
+    })?;
 
     Ok(())
 }
 
 #[instrument(level = "debug", err(Debug), skip_all)]
-fn focus_game(_lua: &Lua, game_root: String) -> LuaResult<()> {
-    focus::focus_game(absolute(game_root)?)?;
+fn focus_neovim(lua: &Lua, (game_root, focus_settings): (String, LuaValue)) -> LuaResult<LuaValue> {
+    let result = focus::focus_neovim(absolute(game_root)?, &lua.from_value(focus_settings)?)?;
 
-    Ok(())
+    lua.to_value_with(
+        &result,
+        LuaSerializeOptions::new().serialize_none_to_null(false),
+    )
 }
 
 #[instrument(level = "debug", err(Debug), skip_all)]
-fn mobdap_install(_lua: &Lua, _: ()) -> LuaResult<String> {
-    let path = mobdap::install()?;
+fn focus_game(lua: &Lua, (game_root, focus_settings): (String, LuaValue)) -> LuaResult<LuaValue> {
+    let result = focus::focus_game(absolute(game_root)?, &lua.from_value(focus_settings)?)?;
+
+    lua.to_value_with(
+        &result,
+        LuaSerializeOptions::new().serialize_none_to_null(false),
+    )
+}
+
+#[instrument(level = "debug", err(Debug), skip_all)]
+fn install_tool(_lua: &Lua, (name, game_root): (String, Option<String>)) -> LuaResult<String> {
+    let root_dir = game_root.map(absolute).transpose()?;
+    let path = tools::install(&name, root_dir.as_deref())?;
     Ok(path
         .to_str()
         .context("could not convert path to string")?
         .to_string())
 }
 
+#[allow(clippy::unnecessary_wraps)]
+#[instrument(level = "debug", err(Debug), skip_all)]
+fn is_offline(_lua: &Lua, _: ()) -> LuaResult<bool> {
+    Ok(offline::is_enabled())
+}
+
+#[instrument(level = "debug", err(Debug), skip_all)]
+fn offline_status(lua: &Lua, game_root: Option<String>) -> LuaResult<LuaTable> {
+    let root_dir = game_root.map(absolute).transpose()?;
+
+    let table = lua.create_table()?;
+    table.set("enabled", offline::is_enabled())?;
+    table.set("degraded", offline::degraded_features(root_dir.as_deref())?)?;
+
+    Ok(table)
+}
+
 #[instrument(level = "debug", err(Debug), skip_all)]
 fn install_dependencies(
     _lua: &Lua,
@@ -4,13 +4,17 @@
 ---@field hot_reload_enabled boolean|nil Enable hot reloading when saving scripts in Neovim
 
 ---@class LauncherSettings Settings for the Neovim launcher run by Defold
----@field type "neovide"|"terminal" Neovim launcher run by Defold
+---@field type "neovide"|"terminal"|"tmux"|"zellij"|"wezterm"|nil Neovim launcher run by Defold, nil means the one from the config files is used, otherwise Neovide or a terminal
 ---@field executable string|nil Executable to be used by the launcher, nil means we're trying to figure this out ourselves
 ---@field socket_type "fsock"|"netsock"|nil Run Neovims RPC protocol over file socket or network. Nil means it will be picked automatic (fsock on Unix, network on Windows)
 ---@field arguments table<string>|nil Extra arguments passed to the `executable` (or neovide)
 ---@field appname string|nil Sets `NVIM_APPNAME` to run the editor in a special configuration
 ---@field debug boolean|nil Enable debug settings for the bridge cli
 
+---@class FocusSettings Settings for switching focus between Neovim and the game
+---@field switcher "hyprctl"|"swaymsg"|"niri"|"kdotool"|"i3-msg"|"gdbus"|"wmctrl"|"xdotool"|"osascript"|nil Tool used to focus windows, nil means it's picked based on the running desktop
+---@field command table<string>|nil Custom command used instead of a switcher, `{class}`, `{title}` and `{pid}` are replaced with the window class, title or the game's process id
+
 ---@class DebuggerSettings Settings for the integrated debugger
 ---@field enable boolean|nil Enable the debugger
 ---@field custom_executable string|nil Use a custom executable for the debugger
@@ -23,6 +27,7 @@
 ---@class DefoldNvimConfig Settings for defold.nvim
 ---@field defold DefoldEditorSettings|nil Settings for the Defold Game Engine
 ---@field launcher LauncherSettings|nil Settings for the Neovim launcher run by Defold
+---@field focus FocusSettings|nil Settings for switching focus between Neovim and the game
 ---@field debugger DebuggerSettings|nil Settings for the integrated debugger
 ---@field keymaps table<string, Keymap>|nil Settings for key -> action mappings
 ---@field force_plugin_enabled boolean|nil Force the plugin to be always enabled (even if we can't find the game.project file)
@@ -37,11 +42,16 @@ local default_config = {
     },
 
     launcher = {
-        type = "neovide",
+        type = nil,
         executable = nil,
         arguments = nil,
     },
 
+    focus = {
+        switcher = nil,
+        command = nil,
+    },
+
     debugger = {
         enable = true,
         custom_executable = nil,
@@ -210,6 +220,11 @@ function M.load_plugin()
     end
     log.debug("Config: " .. vim.inspect(M.config))
 
+    local offline_ok, offline = pcall(sidecar.offline_status, project.project_root())
+    if offline_ok and offline.enabled then
+        log.warn("Offline mode is enabled:\n" .. table.concat(offline.degraded, "\n"))
+    end
+
     -- register hot reload when saving lua files
     if M.config.defold.hot_reload_enabled then
         vim.api.nvim_create_autocmd("BufWritePost", {
@@ -306,17 +321,44 @@ function M.setup_default_editor()
     end
 
     local sidecar = require "defold.sidecar"
-    local ok, err = pcall(sidecar.set_default_editor, port, M.plugin_root(), M.config.launcher)
+    local ok, err = pcall(
+        sidecar.set_default_editor,
+        port,
+        project.project_root(),
+        M.plugin_root(),
+        M.config.launcher,
+        M.config.focus
+    )
 
     if not ok then
         log.error(string.format("Could not set default editor because: %s", err))
     end
 end
 
+---Restores the code editor Defold was using before defold.nvim was set as default editor
+function M.unset_default_editor()
+    local project = require "defold.project"
+    local log = require "defold.service.logger"
+
+    local port = project.editor_port()
+
+    if not port then
+        log.error "Could not find Defold editor, is it running?"
+        return
+    end
+
+    local sidecar = require "defold.sidecar"
+    local ok, err = pcall(sidecar.unset_default_editor, port, project.project_root())
+
+    if not ok then
+        log.error(string.format("Could not unset default editor because: %s", err))
+    end
+end
+
 ---Sets up MobDap as the debugger
 function M.setup_debugger()
     local debugger = require "defold.service.debugger"
-    debugger.setup(M.config.debugger.custom_executable, M.config.debugger.custom_arguments)
+    debugger.setup(M.config.debugger.custom_executable, M.config.debugger.custom_arguments, M.config.focus)
 end
 
 return M
@@ -76,6 +76,12 @@ function M.ensure_nvim_server(socket_type)
     local ok_server, server = pcall(vim.fn.serverstart, addr)
     if ok_server then
         log.debug(string.format("Started new Neovim server at: %s", server))
+
+        local ok_register, err = pcall(sidecar.register_nvim_instance, root, server)
+        if not ok_register then
+            log.error(string.format("Could not register Neovim instance because: %s", err))
+        end
+
         return server
     end
 
@@ -2,6 +2,7 @@ local M = {}
 
 M.custom_executable = nil
 M.custom_arguments = nil
+M.focus_settings = nil
 M.path = nil
 
 ---@return string|nil
@@ -9,6 +10,7 @@ function M.mobdap_path()
     local os = require "defold.service.os"
     local log = require "defold.service.logger"
     local sidecar = require "defold.sidecar"
+    local project = require "defold.project"
 
     if M.custom_executable then
         return M.custom_executable
@@ -22,7 +24,7 @@ function M.mobdap_path()
         return M.path
     end
 
-    local ok, res = pcall(sidecar.mobdap_install)
+    local ok, res = pcall(sidecar.install_tool, "mobdap", project.project_root())
     if not ok then
         log.error(string.format("Could not install mobdap: %s", res))
         return
@@ -34,13 +36,34 @@ end
 
 ---@param custom_executable string|nil
 ---@param custom_arguments table<string>|nil
-function M.setup(custom_executable, custom_arguments)
+---@param focus_settings FocusSettings|nil
+function M.setup(custom_executable, custom_arguments, focus_settings)
     M.custom_executable = custom_executable
     M.custom_arguments = custom_arguments
+    M.focus_settings = focus_settings or {}
 
     M.mobdap_path()
 end
 
+---@param target string
+---@param ok boolean
+---@param res FocusResult|string
+local function report_focus(target, ok, res)
+    local log = require "defold.service.logger"
+
+    if not ok then
+        log.error(string.format("Could not focus %s: %s", target, res))
+        return
+    end
+
+    if not res.backend then
+        log.warn(string.format("Could not focus %s: no focus switcher found", target))
+    elseif not res.window_found then
+        local reason = res.stderr ~= "" and res.stderr or "no matching window"
+        log.warn(string.format("Could not focus %s using %s: %s", target, res.backend, reason))
+    end
+end
+
 function M.register_nvim_dap()
     local log = require "defold.service.logger"
 
@@ -92,10 +115,7 @@ function M.register_nvim_dap()
 
         local rootdir = vim.fs.root(0, { "game.project", ".git" })
 
-        local ok, err = pcall(sidecar.focus_neovim, rootdir)
-        if not ok then
-            log.error(string.format("Could not focus neovim: %s", err))
-        end
+        report_focus("Neovim", pcall(sidecar.focus_neovim, rootdir, M.focus_settings))
     end
 
     dap.listeners.after.continue.defold_nvim_switch_focus_on_continue = function(_, _)
@@ -105,10 +125,7 @@ function M.register_nvim_dap()
 
         local rootdir = vim.fs.root(0, { "game.project", ".git" })
 
-        local ok, err = pcall(sidecar.focus_game, rootdir)
-        if not ok then
-            log.error(string.format("Could not focus neovim: %s", err))
-        end
+        report_focus("the game", pcall(sidecar.focus_game, rootdir, M.focus_settings))
     end
 end
 
@@ -7,6 +7,12 @@ local M = {}
 function M.fetch_release(owner, repository)
     local os = require "defold.service.os"
     local log = require "defold.service.logger"
+
+    if os.is_offline() then
+        log.debug(string.format("Offline mode is enabled, not fetching %s/%s", owner, repository))
+        return nil
+    end
+
     local url = string.format("https://api.github.com/repos/%s/%s/releases/latest", owner, repository)
     local release = os.fetch_json(url)
 
@@ -189,6 +189,19 @@ function M.data_dir()
     return dir
 end
 
+---Offline mode as the sidecar sees it. Until the sidecar is loaded, e.g. while it is being
+---installed, only DEFOLD_NVIM_OFFLINE is known
+---@return boolean
+function M.is_offline()
+    local sidecar = package.loaded["defold_nvim_sidecar"]
+    if sidecar then
+        return sidecar.is_offline()
+    end
+
+    local env = vim.trim(vim.env.DEFOLD_NVIM_OFFLINE or "")
+    return env ~= "" and not vim.tbl_contains({ "0", "false", "no", "off" }, env:lower())
+end
+
 ---Returns `Cache` directory
 ---@return string
 function M.cache_dir()
@@ -124,6 +124,10 @@ local function find_rust_lib_rootdir()
     then
         local curr_version = version()
 
+        if os.is_offline() then
+            return lib_dir
+        end
+
         if not curr_version then
             return download_release()
         end
@@ -158,6 +162,9 @@ local function find_rust_lib_rootdir()
         os.write(version_path(), release.tag_name)
 
         return lib_dir
+    elseif os.is_offline() then
+        log.error "The sidecar is not installed and offline mode is enabled"
+        return nil
     else
         -- and if that also doesnt exist... download it
         return download_release()
@@ -176,6 +183,15 @@ package.cpath = package.cpath
 ---@field title string
 ---@field dependencies string[]
 
+---@class FocusResult
+---@field backend string|nil Focus switcher that ran, nil if none could be found
+---@field window_found boolean
+---@field stderr string
+
+---@class OfflineStatus
+---@field enabled boolean
+---@field degraded string[] What doesn't work because of offline mode
+
 ---@class Sidecar
 ---@field version string
 ---@field set_log_level function(level: "debug"|"info"|"error")
@@ -183,14 +199,18 @@ package.cpath = package.cpath
 ---@field is_editor_port function(port: integer): boolean
 ---@field list_commands function(port: integer): table<string, string>
 ---@field send_command function(port: integer, cmd: string)
----@field set_default_editor function(port: integer, plugin_root: string, launcher_config: LauncherSettings)
+---@field set_default_editor function(port: integer, game_root: string, plugin_root: string, launcher_config: LauncherSettings, focus_config: FocusSettings)
+---@field unset_default_editor function(port: integer, game_root: string)
 ---@field find_bridge_path function(plugin_root: string|nil): string
 ---@field resolve_nvim_server_addr function(game_root: string, socket_type: "fsock"|"netsock"|nil): string
----@field focus_neovim function(game_root: string)
----@field focus_game function(game_root: string)
----@field mobdap_install function(): string
+---@field register_nvim_instance function(game_root: string, addr: string)
+---@field focus_neovim function(game_root: string, focus_config: FocusSettings): FocusResult
+---@field focus_game function(game_root: string, focus_config: FocusSettings): FocusResult
+---@field install_tool function(name: string, game_root: string|nil): string
 ---@field install_dependencies function(game_root: string, force_redownload: boolean|nil)
 ---@field list_dependency_dirs function(game_root: string): string[]
+---@field is_offline function(): boolean
+---@field offline_status function(game_root: string|nil): OfflineStatus
 ---@field data_dir function(): string
 ---@field cache_dir function(): string
 